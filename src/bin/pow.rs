@@ -0,0 +1,318 @@
+//! A command-line interface for solving and verifying proofs of work.
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use blake3_proof_of_work::challenge::{verify_challenge_solution, Challenge};
+use blake3_proof_of_work::{bench, measure, par_search, verify, NONCE_SIZE};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use std::io::Read;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::Duration;
+
+#[derive(Parser)]
+#[command(
+    name = "pow",
+    version,
+    about = "Solve and verify Blake3 proofs of work"
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Search for a nonce proving work over the payload
+    Solve {
+        #[arg(long)]
+        cost: u32,
+        #[command(flatten)]
+        search: SearchArgs,
+        #[command(flatten)]
+        payload: Payload,
+    },
+    /// Check a nonce against the payload, exiting with status 1 if it is invalid
+    Verify {
+        #[arg(long)]
+        cost: u32,
+        /// The nonce, in hex or unpadded URL-safe base64
+        nonce: String,
+        #[command(flatten)]
+        payload: Payload,
+    },
+    /// Print the number of leading zeros a nonce achieves over the payload
+    Measure {
+        /// The nonce, in hex or unpadded URL-safe base64
+        nonce: String,
+        #[command(flatten)]
+        payload: Payload,
+    },
+    /// Measure this machine's hash rate with each number of threads
+    Bench {
+        /// The most worker threads to measure, or one per CPU if zero
+        #[arg(long, default_value_t = 0)]
+        threads: usize,
+        /// How many milliseconds to spend measuring each number of threads
+        #[arg(long, default_value_t = 1000)]
+        millis: u64,
+    },
+    /// Issue, solve and verify server challenges
+    #[command(subcommand)]
+    Challenge(ChallengeCommand),
+}
+
+#[derive(Subcommand)]
+enum ChallengeCommand {
+    /// Mint a challenge, printed in unpadded URL-safe base64
+    Issue {
+        /// The server's secret key, as 64 hex digits
+        #[arg(long)]
+        key: String,
+        #[arg(long)]
+        resource: String,
+        #[arg(long)]
+        cost: u32,
+        /// How many seconds the challenge is valid for
+        #[arg(long, default_value_t = 300)]
+        ttl: u64,
+    },
+    /// Search for a nonce solving a challenge
+    Solve {
+        challenge: String,
+        #[command(flatten)]
+        search: SearchArgs,
+    },
+    /// Check a solution to a challenge, exiting with status 1 if it is invalid
+    Verify {
+        /// The server's secret key, as 64 hex digits
+        #[arg(long)]
+        key: String,
+        #[arg(long)]
+        resource: String,
+        challenge: String,
+        /// The nonce, in hex or unpadded URL-safe base64
+        nonce: String,
+    },
+}
+
+#[derive(Args)]
+struct SearchArgs {
+    /// The most nonces to try before giving up
+    #[arg(long, default_value_t = u32::MAX)]
+    meter: u32,
+    /// The number of worker threads, or one per CPU if zero
+    #[arg(long, default_value_t = 0)]
+    threads: usize,
+    #[arg(long, value_enum, default_value_t = Format::Hex)]
+    format: Format,
+}
+
+/// Where to read the payload from. Without `--data` or `--file`, it is read
+/// from standard input.
+#[derive(Args)]
+struct Payload {
+    /// The payload itself
+    #[arg(long, conflicts_with = "file")]
+    data: Option<String>,
+    /// A file containing the payload
+    #[arg(long)]
+    file: Option<PathBuf>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Hex,
+    Base64,
+}
+
+impl Payload {
+    fn read(&self) -> Result<Vec<u8>, String> {
+        match (&self.data, &self.file) {
+            (Some(data), _) => Ok(data.as_bytes().to_vec()),
+            (None, Some(file)) => {
+                std::fs::read(file).map_err(|e| format!("reading {}: {}", file.display(), e))
+            }
+            (None, None) => {
+                let mut bytes = Vec::new();
+                std::io::stdin()
+                    .read_to_end(&mut bytes)
+                    .map_err(|e| format!("reading standard input: {}", e))?;
+                Ok(bytes)
+            }
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    match run(Cli::parse().command) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::from(1),
+        Err(message) => {
+            eprintln!("pow: {}", message);
+            ExitCode::from(2)
+        }
+    }
+}
+
+/// Runs `command`, returning whether any proof it checked was valid.
+fn run(command: Command) -> Result<bool, String> {
+    match command {
+        Command::Solve {
+            cost,
+            search,
+            payload,
+        } => solve(&payload.read()?, cost, &search),
+        Command::Verify {
+            cost,
+            nonce,
+            payload,
+        } => report(verify(&payload.read()?, parse_nonce(&nonce)?, cost)),
+        Command::Measure { nonce, payload } => {
+            println!("{}", measure(&payload.read()?, parse_nonce(&nonce)?));
+            Ok(true)
+        }
+        Command::Bench { threads, millis } => {
+            for benchmark in bench(threads, Duration::from_millis(millis)) {
+                println!(
+                    "{} threads: {:.0} hashes/s",
+                    benchmark.threads, benchmark.hash_rate
+                );
+            }
+            Ok(true)
+        }
+        Command::Challenge(ChallengeCommand::Issue {
+            key,
+            resource,
+            cost,
+            ttl,
+        }) => {
+            let challenge = Challenge::issue(
+                &parse_key(&key)?,
+                resource.as_bytes(),
+                cost,
+                Duration::from_secs(ttl),
+            )
+            .map_err(|e| e.to_string())?;
+            println!("{}", URL_SAFE_NO_PAD.encode(challenge.to_bytes()));
+            Ok(true)
+        }
+        Command::Challenge(ChallengeCommand::Solve { challenge, search }) => {
+            let challenge = parse_challenge(&challenge)?;
+            solve(&challenge.to_bytes(), challenge.cost(), &search)
+        }
+        Command::Challenge(ChallengeCommand::Verify {
+            key,
+            resource,
+            challenge,
+            nonce,
+        }) => match verify_challenge_solution(
+            &parse_key(&key)?,
+            &parse_challenge(&challenge)?,
+            resource.as_bytes(),
+            parse_nonce(&nonce)?,
+        ) {
+            Ok(()) => report(true),
+            Err(e) => {
+                println!("invalid: {}", e);
+                Ok(false)
+            }
+        },
+    }
+}
+
+fn solve(bytes: &[u8], cost: u32, search: &SearchArgs) -> Result<bool, String> {
+    let nonce = par_search(bytes, cost, search.meter, search.threads).map_err(|e| e.to_string())?;
+    match search.format {
+        Format::Hex => println!("{}", to_hex(&nonce)),
+        Format::Base64 => println!("{}", URL_SAFE_NO_PAD.encode(nonce)),
+    }
+    Ok(true)
+}
+
+fn report(valid: bool) -> Result<bool, String> {
+    println!("{}", if valid { "valid" } else { "invalid" });
+    Ok(valid)
+}
+
+/// Parses a nonce in either hex or unpadded URL-safe base64, which can be told
+/// apart by their lengths.
+fn parse_nonce(nonce: &str) -> Result<[u8; NONCE_SIZE], String> {
+    let bytes = if nonce.len() == 2 * NONCE_SIZE {
+        from_hex(nonce)
+    } else {
+        URL_SAFE_NO_PAD.decode(nonce).ok()
+    };
+    bytes
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| format!("invalid nonce {:?}", nonce))
+}
+
+fn parse_key(key: &str) -> Result<[u8; blake3::KEY_LEN], String> {
+    from_hex(key)
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| format!("key must be {} hex digits", 2 * blake3::KEY_LEN))
+}
+
+fn parse_challenge(challenge: &str) -> Result<Challenge, String> {
+    let bytes = URL_SAFE_NO_PAD
+        .decode(challenge)
+        .map_err(|_| "challenge is not unpadded URL-safe base64".to_string())?;
+    Challenge::from_bytes(&bytes).map_err(|e| e.to_string())
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    let pairs = hex.as_bytes().chunks_exact(2);
+    if !pairs.remainder().is_empty() || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    pairs
+        .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_round_trips() {
+        let bytes = [0, 1, 0x7f, 0x80, 0xab, 0xff];
+        assert_eq!(to_hex(&bytes), "00017f80abff");
+        assert_eq!(from_hex("00017f80abff"), Some(bytes.to_vec()));
+        assert_eq!(from_hex("00017F80ABFF"), Some(bytes.to_vec()));
+        assert_eq!(from_hex(""), Some(Vec::new()));
+        assert_eq!(from_hex("abc"), None);
+        assert_eq!(from_hex("zz"), None);
+        assert_eq!(from_hex("+1"), None);
+        assert_eq!(from_hex("é1"), None);
+    }
+
+    #[test]
+    fn nonces_parse_from_hex_or_base64() {
+        let nonce = [0, 1, 2, 3, 4, 5, 6, 7, 0xfe, 0xff];
+        assert_eq!(parse_nonce(&to_hex(&nonce)), Ok(nonce));
+        assert_eq!(parse_nonce(&URL_SAFE_NO_PAD.encode(nonce)), Ok(nonce));
+        // Twenty characters are always taken as hex, even if they would also
+        // be valid base64 of some other length.
+        assert!(parse_nonce("0123456789abcdefghij").is_err());
+        assert!(parse_nonce(&to_hex(&nonce[..9])).is_err());
+        assert!(parse_nonce(&URL_SAFE_NO_PAD.encode([0; 11])).is_err());
+        assert!(parse_nonce(&(URL_SAFE_NO_PAD.encode(nonce) + "=")).is_err());
+        assert!(parse_nonce("").is_err());
+    }
+
+    #[test]
+    fn keys_are_hex() {
+        let key = [0x5a; blake3::KEY_LEN];
+        assert_eq!(parse_key(&to_hex(&key)), Ok(key));
+        assert!(parse_key(&to_hex(&key[..31])).is_err());
+        assert!(parse_key(&(to_hex(&key) + "00")).is_err());
+        assert!(parse_key(&"g".repeat(64)).is_err());
+        assert!(parse_key(&URL_SAFE_NO_PAD.encode(key)).is_err());
+    }
+}
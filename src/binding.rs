@@ -0,0 +1,113 @@
+//! Binding proofs of work to HTTP requests.
+//!
+//! A server demanding work sends a fresh `Challenge` in the
+//! `CHALLENGE_HEADER`. The client then searches for a `Proof` over the bytes
+//! returned by `proof_input`, which covers that challenge together with the
+//! `request_binding` of the request, and sends the challenge and the proof
+//! back in the `PROOF_HEADER`. A proof is therefore useless for any other
+//! request, and once the challenge expires it is useless altogether.
+
+use crate::challenge::Challenge;
+use crate::proof::Proof;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use http::header::{HeaderName, HeaderValue};
+use http::{Method, Uri};
+
+/// The default header carrying a challenge and a `Proof` solving it, as
+/// produced by `proof_value`.
+pub const PROOF_HEADER: HeaderName = HeaderName::from_static("x-proof-of-work");
+
+/// The header in which a server issues a `Challenge`, as produced by
+/// `challenge_value`.
+pub const CHALLENGE_HEADER: HeaderName = HeaderName::from_static("x-proof-of-work-challenge");
+
+/// The bytes identifying a request with the given `method`, `uri` and `body`.
+///
+/// This is the hash of the method, the path and query of the `uri` and the
+/// hash of the `body`. The scheme and authority of the `uri` are left out, as
+/// servers rarely see the same ones as clients do behind a proxy.
+pub fn request_binding(method: &Method, uri: &Uri, body: &[u8]) -> [u8; blake3::OUT_LEN] {
+    let path = uri.path_and_query().map_or("/", |path| path.as_str());
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(method.as_str().as_bytes());
+    hasher.update(&[0]);
+    hasher.update(path.as_bytes());
+    hasher.update(&[0]);
+    hasher.update(blake3::hash(body).as_bytes());
+    *hasher.finalize().as_bytes()
+}
+
+/// The bytes a proof solving `challenge` for a request with the given
+/// `request_binding` is computed over: the encoded challenge followed by the
+/// binding.
+pub fn proof_input(challenge: &Challenge, binding: &[u8; blake3::OUT_LEN]) -> Vec<u8> {
+    let mut bytes = challenge.to_bytes();
+    bytes.extend_from_slice(binding);
+    bytes
+}
+
+/// The value of the `CHALLENGE_HEADER` issuing `challenge`, which is its
+/// encoding in unpadded URL-safe base64.
+pub fn challenge_value(challenge: &Challenge) -> HeaderValue {
+    // Base64 is always a valid header value.
+    HeaderValue::try_from(URL_SAFE_NO_PAD.encode(challenge.to_bytes())).unwrap()
+}
+
+/// The `Challenge` in a value of the `CHALLENGE_HEADER`, if it is well formed.
+/// This does not check that the challenge is authentic.
+pub fn parse_challenge(value: &HeaderValue) -> Option<Challenge> {
+    let bytes = URL_SAFE_NO_PAD.decode(value.as_bytes()).ok()?;
+    Challenge::from_bytes(&bytes).ok()
+}
+
+/// The value of the `PROOF_HEADER` answering `challenge` with `proof`, which
+/// is the encoded challenge and proof separated by a `.`.
+pub fn proof_value(challenge: &Challenge, proof: &Proof) -> HeaderValue {
+    let value = format!("{}.{}", URL_SAFE_NO_PAD.encode(challenge.to_bytes()), proof);
+    HeaderValue::try_from(value).unwrap()
+}
+
+/// The `Challenge` and `Proof` in a value of the `PROOF_HEADER`, if it is well
+/// formed.
+pub fn parse_proof(value: &HeaderValue) -> Option<(Challenge, Proof)> {
+    let (challenge, proof) = value.to_str().ok()?.split_once('.')?;
+    let challenge = URL_SAFE_NO_PAD.decode(challenge).ok()?;
+    Some((Challenge::from_bytes(&challenge).ok()?, proof.parse().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Scheme;
+    use std::time::Duration;
+
+    #[test]
+    fn binding_covers_method_path_and_body() {
+        let uri: Uri = "https://example.com/api/items?page=2".parse().unwrap();
+        let binding = request_binding(&Method::POST, &uri, b"{}");
+        let relative: Uri = "/api/items?page=2".parse().unwrap();
+        assert_eq!(request_binding(&Method::POST, &relative, b"{}"), binding);
+        assert_ne!(request_binding(&Method::PUT, &uri, b"{}"), binding);
+        assert_ne!(request_binding(&Method::POST, &uri, b"[]"), binding);
+        let other: Uri = "/api/items?page=3".parse().unwrap();
+        assert_ne!(request_binding(&Method::POST, &other, b"{}"), binding);
+    }
+
+    #[test]
+    fn header_values_round_trip() -> Result<(), rand::Error> {
+        let challenge = Challenge::issue(&[7; 32], b"", 20, Duration::from_secs(60))?;
+        let value = challenge_value(&challenge);
+        assert_eq!(parse_challenge(&value), Some(challenge.clone()));
+        let proof = Proof {
+            scheme: Scheme::default(),
+            cost: 20,
+            nonce: [9; crate::NONCE_SIZE],
+        };
+        let value = proof_value(&challenge, &proof);
+        assert_eq!(parse_proof(&value), Some((challenge, proof)));
+        assert_eq!(parse_proof(&HeaderValue::from_static("cost=20")), None);
+        assert_eq!(parse_challenge(&HeaderValue::from_static("cost=20")), None);
+        Ok(())
+    }
+}
@@ -0,0 +1,115 @@
+//! Measuring this machine's hash rate, and choosing difficulties from it.
+
+use crate::{par_search, Target};
+use std::time::{Duration, Instant};
+
+/// The length of the input hashed by `bench`, which is about the size of an
+/// encoded `Challenge`.
+const BENCH_LEN: usize = 96;
+
+/// How long `calibrate` and `calibrate_target` spend measuring.
+const CALIBRATION_SAMPLE: Duration = Duration::from_millis(100);
+
+/// The hash rate achieved with some number of threads, as found by `bench`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Benchmark {
+    pub threads: usize,
+    /// The number of `nonce`s tried per second.
+    pub hash_rate: f64,
+}
+
+/// # Hash rate measurement
+///
+/// Measures how many `nonce`s per second `par_search` tries on this machine
+/// with the given number of `threads` for inputs of `len` bytes, spending
+/// roughly between `sample` and twice `sample` doing so. With one thread,
+/// this is the rate of `search`.
+///
+/// The time each attempt takes grows with the length of the input, so `len`
+/// should be that of the inputs proofs will actually be searched for.
+pub fn hash_rate(threads: usize, len: usize, sample: Duration) -> f64 {
+    let payload = vec![0x5a; len];
+    let mut attempts = 1024u32;
+    loop {
+        let start = Instant::now();
+        // No hash has more than 256 leading zeros, so this always tries
+        // exactly `attempts` nonces, one more than the meter allows.
+        let _ = par_search(&payload, u32::MAX, attempts - 1, threads);
+        let elapsed = start.elapsed();
+        if elapsed >= sample || attempts == u32::MAX {
+            return attempts as f64 / elapsed.as_secs_f64();
+        }
+        attempts = attempts.saturating_mul(2);
+    }
+}
+
+/// # Benchmarking
+///
+/// Measures the hash rate of this machine with every number of threads from
+/// one up to `max_threads`, spending roughly `sample` on each, for inputs
+/// about the size of an encoded `Challenge`. If `max_threads` is zero, it
+/// goes up to the number of logical CPUs.
+pub fn bench(max_threads: usize, sample: Duration) -> Vec<Benchmark> {
+    let max_threads = if max_threads == 0 {
+        num_cpus::get()
+    } else {
+        max_threads
+    };
+    (1..=max_threads)
+        .map(|threads| Benchmark {
+            threads,
+            hash_rate: hash_rate(threads, BENCH_LEN, sample),
+        })
+        .collect()
+}
+
+/// # Difficulty calibration
+///
+/// Chooses the `cost` for which `search` over inputs of `len` bytes is
+/// expected to take closest to `target` on this machine.
+pub fn calibrate(len: usize, target: Duration) -> u32 {
+    let work = hash_rate(1, len, CALIBRATION_SAMPLE) * target.as_secs_f64();
+    work.max(1.0).log2().round() as u32
+}
+
+/// # Fractional difficulty calibration
+///
+/// Chooses the `Target` for which `search_target` over inputs of `len` bytes
+/// is expected to take `target` on this machine.
+pub fn calibrate_target(len: usize, target: Duration) -> Target {
+    Target::from_work(hash_rate(1, len, CALIBRATION_SAMPLE) * target.as_secs_f64())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bench_measures_every_thread_count() {
+        let benchmarks = bench(2, Duration::from_millis(10));
+        assert_eq!(benchmarks.len(), 2);
+        for (i, benchmark) in benchmarks.iter().enumerate() {
+            assert_eq!(benchmark.threads, i + 1);
+            assert!(benchmark.hash_rate > 0.0);
+        }
+    }
+
+    #[test]
+    fn calibration_grows_with_time() {
+        let short = calibrate(BENCH_LEN, Duration::from_millis(1));
+        let long = calibrate(BENCH_LEN, Duration::from_secs(10));
+        assert!(long >= short + 10);
+        assert_eq!(calibrate(BENCH_LEN, Duration::ZERO), 0);
+        assert!(
+            calibrate_target(BENCH_LEN, Duration::from_secs(10))
+                < calibrate_target(BENCH_LEN, Duration::from_millis(1))
+        );
+    }
+
+    #[test]
+    fn large_inputs_hash_slowly() {
+        let small = hash_rate(1, BENCH_LEN, Duration::from_millis(20));
+        let large = hash_rate(1, 1 << 16, Duration::from_millis(20));
+        assert!(large * 16.0 < small);
+    }
+}
@@ -0,0 +1,309 @@
+//! Stateless challenges issued by a server.
+//!
+//! A server which requires proofs of work should not let clients choose the
+//! `bytes` they prove work over, or they could reuse old proofs or pick inputs
+//! which happen to be easy. Instead, the server mints a `Challenge` containing
+//! a random salt, an expiry, the required `cost` and the resource it grants
+//! access to, all authenticated with `blake3::keyed_hash` under a secret key.
+//! The client searches for a `nonce` over the encoded challenge and sends both
+//! back, and the server checks them with `verify_challenge_solution` without
+//! having remembered anything about the challenge.
+
+use crate::NONCE_SIZE;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub const SALT_SIZE: usize = 16usize;
+
+/// The length of the encoding of a `Challenge` for an empty resource.
+const HEADER_SIZE: usize = SALT_SIZE + 8 + 8 + 4 + 4;
+
+/// Errors which can occur in decoding or verifying a challenge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    Malformed,
+    BadMac,
+    Expired,
+    WrongResource,
+    InsufficientWork,
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Malformed => write!(f, "malformed challenge"),
+            Error::BadMac => write!(f, "challenge was not issued with this key"),
+            Error::Expired => write!(f, "challenge has expired"),
+            Error::WrongResource => write!(f, "challenge is for a different resource"),
+            Error::InsufficientWork => write!(f, "nonce does not meet the challenge's cost"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// A challenge minted by a server, which a client must solve by finding a
+/// proof of work over its encoding at its `cost`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Challenge {
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_support::array"))]
+    salt: [u8; SALT_SIZE],
+    issued_at: u64,
+    expires_at: u64,
+    cost: u32,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_support::vec"))]
+    resource: Vec<u8>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_support::array"))]
+    mac: [u8; blake3::OUT_LEN],
+}
+
+impl Challenge {
+    /// Mints a challenge for `resource` at the given `cost`, valid for `ttl`
+    /// from now.
+    pub fn issue(
+        key: &[u8; blake3::KEY_LEN],
+        resource: &[u8],
+        cost: u32,
+        ttl: Duration,
+    ) -> Result<Challenge, rand::Error> {
+        Challenge::issue_at(key, resource, cost, SystemTime::now(), ttl)
+    }
+
+    /// Mints a challenge for `resource` at the given `cost`, valid for `ttl`
+    /// from `now`.
+    pub fn issue_at(
+        key: &[u8; blake3::KEY_LEN],
+        resource: &[u8],
+        cost: u32,
+        now: SystemTime,
+        ttl: Duration,
+    ) -> Result<Challenge, rand::Error> {
+        use rand::Fill;
+        let mut salt = [0u8; SALT_SIZE];
+        salt.try_fill(&mut rand::thread_rng())?;
+        let issued_at = unix_seconds(now);
+        let mut challenge = Challenge {
+            salt,
+            issued_at,
+            expires_at: issued_at.saturating_add(ttl.as_secs()),
+            cost,
+            resource: resource.to_vec(),
+            mac: [0; blake3::OUT_LEN],
+        };
+        challenge.mac = challenge.compute_mac(key);
+        Ok(challenge)
+    }
+
+    /// The random salt making this challenge unique.
+    pub fn salt(&self) -> &[u8; SALT_SIZE] {
+        &self.salt
+    }
+
+    /// When this challenge was issued, in seconds since the Unix epoch.
+    pub fn issued_at(&self) -> u64 {
+        self.issued_at
+    }
+
+    /// When this challenge expires, in seconds since the Unix epoch.
+    pub fn expires_at(&self) -> u64 {
+        self.expires_at
+    }
+
+    /// The `cost` a solution to this challenge must meet.
+    pub fn cost(&self) -> u32 {
+        self.cost
+    }
+
+    /// The resource this challenge grants access to.
+    pub fn resource(&self) -> &[u8] {
+        &self.resource
+    }
+
+    /// Searches for a `nonce` solving this challenge, giving up after `meter`
+    /// attempts just as `search` does.
+    pub fn solve(&self, meter: u32) -> Result<[u8; NONCE_SIZE], crate::Error> {
+        crate::search(&self.to_bytes(), self.cost, meter)
+    }
+
+    /// The canonical encoding of this challenge, which is also the input a
+    /// proof of work is computed over.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.unauthenticated_bytes();
+        bytes.extend_from_slice(&self.mac);
+        bytes
+    }
+
+    /// Decodes a challenge from its canonical encoding. This does not check
+    /// that the challenge is authentic.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Challenge, Error> {
+        if bytes.len() < HEADER_SIZE + blake3::OUT_LEN {
+            return Err(Error::Malformed);
+        }
+        let (salt, rest) = bytes.split_at(SALT_SIZE);
+        let (issued_at, rest) = rest.split_at(8);
+        let (expires_at, rest) = rest.split_at(8);
+        let (cost, rest) = rest.split_at(4);
+        let (resource_len, rest) = rest.split_at(4);
+        let resource_len = u32::from_be_bytes(resource_len.try_into().unwrap()) as usize;
+        if rest.len() != resource_len + blake3::OUT_LEN {
+            return Err(Error::Malformed);
+        }
+        let (resource, mac) = rest.split_at(resource_len);
+        Ok(Challenge {
+            salt: salt.try_into().unwrap(),
+            issued_at: u64::from_be_bytes(issued_at.try_into().unwrap()),
+            expires_at: u64::from_be_bytes(expires_at.try_into().unwrap()),
+            cost: u32::from_be_bytes(cost.try_into().unwrap()),
+            resource: resource.to_vec(),
+            mac: mac.try_into().unwrap(),
+        })
+    }
+
+    fn unauthenticated_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_SIZE + self.resource.len() + blake3::OUT_LEN);
+        bytes.extend_from_slice(&self.salt);
+        bytes.extend_from_slice(&self.issued_at.to_be_bytes());
+        bytes.extend_from_slice(&self.expires_at.to_be_bytes());
+        bytes.extend_from_slice(&self.cost.to_be_bytes());
+        bytes.extend_from_slice(&(self.resource.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(&self.resource);
+        bytes
+    }
+
+    fn compute_mac(&self, key: &[u8; blake3::KEY_LEN]) -> [u8; blake3::OUT_LEN] {
+        blake3::keyed_hash(key, &self.unauthenticated_bytes()).into()
+    }
+}
+
+/// # Challenge verification
+///
+/// Checks that `challenge` was issued under `key` for `resource`, has not
+/// expired, and that `nonce` is a proof of work over it at its `cost`.
+pub fn verify_challenge_solution(
+    key: &[u8; blake3::KEY_LEN],
+    challenge: &Challenge,
+    resource: &[u8],
+    nonce: [u8; NONCE_SIZE],
+) -> Result<(), Error> {
+    verify_challenge_solution_at(key, challenge, resource, nonce, SystemTime::now())
+}
+
+/// # Challenge verification at a given time
+///
+/// Performs the same checks as `verify_challenge_solution`, treating `now` as
+/// the current time.
+pub fn verify_challenge_solution_at(
+    key: &[u8; blake3::KEY_LEN],
+    challenge: &Challenge,
+    resource: &[u8],
+    nonce: [u8; NONCE_SIZE],
+    now: SystemTime,
+) -> Result<(), Error> {
+    verify_challenge_at(key, challenge, resource, now)?;
+    if !crate::verify(&challenge.to_bytes(), nonce, challenge.cost) {
+        return Err(Error::InsufficientWork);
+    }
+    Ok(())
+}
+
+/// # Challenge authentication
+///
+/// Checks that `challenge` was issued under `key` for `resource` and has not
+/// expired, without looking at any proof of work. This is for protocols which
+/// compute proofs over more than the challenge itself.
+pub fn verify_challenge(
+    key: &[u8; blake3::KEY_LEN],
+    challenge: &Challenge,
+    resource: &[u8],
+) -> Result<(), Error> {
+    verify_challenge_at(key, challenge, resource, SystemTime::now())
+}
+
+/// # Challenge authentication at a given time
+///
+/// Performs the same checks as `verify_challenge`, treating `now` as the
+/// current time.
+pub fn verify_challenge_at(
+    key: &[u8; blake3::KEY_LEN],
+    challenge: &Challenge,
+    resource: &[u8],
+    now: SystemTime,
+) -> Result<(), Error> {
+    // Comparing `blake3::Hash`es takes constant time.
+    if blake3::Hash::from(challenge.compute_mac(key)) != blake3::Hash::from(challenge.mac) {
+        return Err(Error::BadMac);
+    }
+    if unix_seconds(now) > challenge.expires_at {
+        return Err(Error::Expired);
+    }
+    if challenge.resource != resource {
+        return Err(Error::WrongResource);
+    }
+    Ok(())
+}
+
+fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: [u8; blake3::KEY_LEN] = [7; blake3::KEY_LEN];
+
+    #[test]
+    fn challenge_round_trips() -> Result<(), rand::Error> {
+        let challenge = Challenge::issue(&KEY, b"GET /widgets", 12, Duration::from_secs(60))?;
+        assert_eq!(
+            Challenge::from_bytes(&challenge.to_bytes()),
+            Ok(challenge.clone())
+        );
+        let bytes = challenge.to_bytes();
+        assert_eq!(
+            Challenge::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(Error::Malformed)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn verify_challenge_solution_works() -> Result<(), crate::Error> {
+        let resource = b"GET /widgets";
+        let now = SystemTime::now();
+        let challenge = Challenge::issue_at(&KEY, resource, 12, now, Duration::from_secs(60))?;
+        let nonce = challenge.solve(100000000)?;
+        assert_eq!(
+            verify_challenge_solution_at(&KEY, &challenge, resource, nonce, now),
+            Ok(())
+        );
+        assert_eq!(
+            verify_challenge_solution_at(&[8; blake3::KEY_LEN], &challenge, resource, nonce, now),
+            Err(Error::BadMac)
+        );
+        assert_eq!(
+            verify_challenge_solution_at(
+                &KEY,
+                &challenge,
+                resource,
+                nonce,
+                now + Duration::from_secs(61)
+            ),
+            Err(Error::Expired)
+        );
+        assert_eq!(
+            verify_challenge_solution_at(&KEY, &challenge, b"GET /gadgets", nonce, now),
+            Err(Error::WrongResource)
+        );
+        let mut tampered = challenge.to_bytes();
+        tampered[SALT_SIZE + 16 + 3] = 0;
+        let tampered = Challenge::from_bytes(&tampered).unwrap();
+        assert_eq!(
+            verify_challenge_solution_at(&KEY, &tampered, resource, nonce, now),
+            Err(Error::BadMac)
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,121 @@
+//! Answering the proof of work challenges of HTTP servers.
+//!
+//! When a server such as one behind a `ProofOfWorkLayer` rejects a request
+//! with a `401` or `429` carrying a `Challenge` in the `CHALLENGE_HEADER`, the
+//! client solves the challenge for that request and retries it with the
+//! result in the `PROOF_HEADER`. Nothing here depends on how requests are
+//! sent, so this works with any HTTP client built on `http`:
+//!
+//! ```ignore
+//! let response = send(&request);
+//! if let Some(proof) = client::solve_challenge(&request, &response, u32::MAX)? {
+//!     request.headers_mut().insert(binding::PROOF_HEADER, proof);
+//!     let response = send(&request);
+//! }
+//! ```
+//!
+//! Each challenge may only be used once and expires soon after it is issued,
+//! so a request should be retried as soon as the challenge is solved.
+
+use crate::binding::{
+    parse_challenge, proof_input, proof_value, request_binding, CHALLENGE_HEADER,
+};
+use crate::challenge::Challenge;
+use crate::proof::Proof;
+use crate::{Error, Scheme};
+use http::header::HeaderValue;
+use http::{Method, Request, Response, StatusCode, Uri};
+
+/// The `Challenge` issued by `response`, if it is a challenge.
+pub fn challenge<B>(response: &Response<B>) -> Option<Challenge> {
+    match response.status() {
+        StatusCode::UNAUTHORIZED | StatusCode::TOO_MANY_REQUESTS => {
+            parse_challenge(response.headers().get(CHALLENGE_HEADER)?)
+        }
+        _ => None,
+    }
+}
+
+/// Searches for a proof solving `challenge` for a request with the given
+/// `method`, `uri` and `body`, giving up after `meter` attempts just as
+/// `search` does, and returns the value of the `PROOF_HEADER` to send it with.
+pub fn solve(
+    challenge: &Challenge,
+    method: &Method,
+    uri: &Uri,
+    body: &[u8],
+    meter: u32,
+) -> Result<HeaderValue, Error> {
+    let input = proof_input(challenge, &request_binding(method, uri, body));
+    let proof = Proof::search(&input, challenge.cost(), meter, Scheme::default())?;
+    Ok(proof_value(challenge, &proof))
+}
+
+/// Searches for a proof solving `challenge` for `request`, as `solve` does.
+pub fn solve_request<B: AsRef<[u8]>>(
+    challenge: &Challenge,
+    request: &Request<B>,
+    meter: u32,
+) -> Result<HeaderValue, Error> {
+    solve(
+        challenge,
+        request.method(),
+        request.uri(),
+        request.body().as_ref(),
+        meter,
+    )
+}
+
+/// If `response` to `request` is a challenge, solves it as `solve` does,
+/// returning the value of the `PROOF_HEADER` to retry `request` with.
+pub fn solve_challenge<B: AsRef<[u8]>, R>(
+    request: &Request<B>,
+    response: &Response<R>,
+    meter: u32,
+) -> Result<Option<HeaderValue>, Error> {
+    match challenge(response) {
+        Some(challenge) => solve_request(&challenge, request, meter).map(Some),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binding::{challenge_value, parse_proof};
+    use std::time::Duration;
+
+    fn respond(status: StatusCode, challenge: &Challenge) -> Response<()> {
+        let mut response = Response::new(());
+        *response.status_mut() = status;
+        response
+            .headers_mut()
+            .insert(CHALLENGE_HEADER, challenge_value(challenge));
+        response
+    }
+
+    #[test]
+    fn solves_challenges() -> Result<(), Error> {
+        let issued = Challenge::issue(&[7; 32], b"", 8, Duration::from_secs(60))?;
+        let request = Request::put("/items/3").body(b"{}".to_vec()).unwrap();
+        let response = respond(StatusCode::TOO_MANY_REQUESTS, &issued);
+        assert_eq!(challenge(&response), Some(issued.clone()));
+        let value = solve_challenge(&request, &response, u32::MAX)?.unwrap();
+        let (answered, proof) = parse_proof(&value).unwrap();
+        assert_eq!(answered, issued);
+        let binding = request_binding(request.method(), request.uri(), request.body());
+        assert!(proof.verify(&proof_input(&issued, &binding), 8));
+
+        let ok = respond(StatusCode::OK, &issued);
+        assert_eq!(solve_challenge(&request, &ok, u32::MAX)?, None);
+        let mut unchallenged = respond(StatusCode::UNAUTHORIZED, &issued);
+        unchallenged.headers_mut().remove(CHALLENGE_HEADER);
+        assert_eq!(challenge(&unchallenged), None);
+        let hard = Challenge::issue(&[7; 32], b"", 200, Duration::from_secs(60))?;
+        assert!(matches!(
+            solve_request(&hard, &request, 10),
+            Err(Error::MeterOverdrawn)
+        ));
+        Ok(())
+    }
+}
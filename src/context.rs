@@ -0,0 +1,57 @@
+//! Domain separation of proofs of work.
+
+use crate::NONCE_SIZE;
+use std::fmt;
+
+/// An application-specific context string which proofs are bound to.
+///
+/// Proofs searched for with `search_in` hash the `nonce` and the `bytes`
+/// with Blake3 in key derivation mode, keyed by the context string, rather
+/// than with plain Blake3. A proof made in one context is therefore no good
+/// in any other, nor for `verify`, even for the same `bytes`.
+///
+/// As with `blake3::derive_key`, the context string should be hardcoded,
+/// globally unique and application-specific, such as
+/// `"example.com 2024-06-01 login proof of work"`.
+#[derive(Clone)]
+pub struct Context {
+    context: String,
+    /// The hasher state after absorbing the context string.
+    hasher: blake3::Hasher,
+}
+
+impl Context {
+    pub fn new(context: &str) -> Context {
+        Context {
+            context: context.to_string(),
+            hasher: blake3::Hasher::new_derive_key(context),
+        }
+    }
+
+    /// The context string.
+    pub fn as_str(&self) -> &str {
+        &self.context
+    }
+
+    /// The hash of `nonce` followed by `bytes` in this context.
+    pub(crate) fn hash(&self, bytes: &[u8], nonce: &[u8; NONCE_SIZE]) -> blake3::Hash {
+        let mut hasher = self.hasher.clone();
+        hasher.update(nonce);
+        hasher.update(bytes);
+        hasher.finalize()
+    }
+}
+
+impl fmt::Debug for Context {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Context").field(&self.context).finish()
+    }
+}
+
+impl PartialEq for Context {
+    fn eq(&self, other: &Context) -> bool {
+        self.context == other.context
+    }
+}
+
+impl Eq for Context {}
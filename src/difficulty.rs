@@ -0,0 +1,556 @@
+//! Choosing the `cost` to demand of clients.
+//!
+//! A `DifficultyController` tracks how often each client makes requests, and
+//! demands more work of those making many. The cost it demands can be put in
+//! a `Challenge`, so that the server later checks the proof against the cost
+//! it actually issued.
+//!
+//! A `LoadController` instead adjusts a single server-wide cost according to
+//! how quickly solutions arrive, so that floods automatically get more
+//! expensive.
+
+use crate::challenge::Challenge;
+use crate::Target;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Decayed request counts below this are forgotten when pruning.
+const NEGLIGIBLE: f64 = 1e-3;
+
+/// The fewest clients tracked before idle ones are pruned.
+const MIN_PRUNE: usize = 1024usize;
+
+/// The default most clients tracked at once.
+const DEFAULT_MAX_CLIENTS: usize = 1 << 20;
+
+/// Tracks request rates per client and chooses the `cost` each must pay.
+///
+/// Each client has a request count which halves every `half_life`. While that
+/// count is at most `allowance`, the client is asked for `min_cost`, and each
+/// time it doubles beyond that the cost goes up by one, up to `max_cost`. A
+/// client which stops making requests thus decays back to `min_cost`.
+///
+/// Clients are identified by any key, such as an IP address, account or API
+/// token, and a single controller may be shared between many threads. At
+/// most `max_clients` are tracked at once. Once that many have made requests
+/// recently, any other client is asked for `max_cost`, so that a flood of
+/// requests from ever-changing keys cannot exhaust memory or evade the
+/// controller.
+#[derive(Debug)]
+pub struct DifficultyController<K> {
+    min_cost: u32,
+    max_cost: u32,
+    half_life: Duration,
+    allowance: f64,
+    max_clients: usize,
+    clients: Mutex<Clients<K>>,
+}
+
+#[derive(Debug)]
+struct Clients<K> {
+    counts: HashMap<K, Count>,
+    next_prune: usize,
+    /// When pruning may next happen, so that a full controller is not
+    /// pruned on every request.
+    prune_after: Instant,
+}
+
+/// A request count as of some instant.
+#[derive(Debug, Clone, Copy)]
+struct Count {
+    value: f64,
+    at: Instant,
+}
+
+impl<K: Hash + Eq + Clone> DifficultyController<K> {
+    /// Constructs a controller demanding between `min_cost` and `max_cost`,
+    /// which lets each client make `allowance` requests per `half_life` at
+    /// `min_cost`.
+    ///
+    /// # Panics
+    ///
+    /// If `half_life` is zero or `allowance` is not positive and finite.
+    pub fn new(
+        min_cost: u32,
+        max_cost: u32,
+        half_life: Duration,
+        allowance: f64,
+    ) -> DifficultyController<K> {
+        assert!(!half_life.is_zero(), "half_life must not be zero");
+        assert!(
+            allowance > 0.0 && allowance.is_finite(),
+            "allowance must be positive and finite"
+        );
+        DifficultyController {
+            min_cost,
+            max_cost: max_cost.max(min_cost),
+            half_life,
+            allowance,
+            max_clients: DEFAULT_MAX_CLIENTS,
+            clients: Mutex::new(Clients {
+                counts: HashMap::new(),
+                next_prune: MIN_PRUNE,
+                prune_after: Instant::now(),
+            }),
+        }
+    }
+
+    /// Tracks at most `max_clients` clients at once, rather than about a
+    /// million.
+    pub fn max_clients(mut self, max_clients: usize) -> DifficultyController<K> {
+        self.max_clients = max_clients;
+        self.clients.get_mut().unwrap().next_prune = MIN_PRUNE.min(max_clients);
+        self
+    }
+
+    /// The `cost` to demand of the client `key` for its next request.
+    pub fn cost(&self, key: &K) -> u32 {
+        self.cost_at(key, Instant::now())
+    }
+
+    /// Records a request by the client `key`, returning the `cost` it was
+    /// required to pay for it.
+    pub fn record(&self, key: &K) -> u32 {
+        self.record_at(key, Instant::now())
+    }
+
+    /// Records a request by the client `key` and mints a `Challenge` for
+    /// `resource` at the `cost` it is required to pay, so that
+    /// `verify_challenge_solution` later checks against that same cost.
+    pub fn issue_challenge(
+        &self,
+        key: &K,
+        server_key: &[u8; blake3::KEY_LEN],
+        resource: &[u8],
+        ttl: Duration,
+    ) -> Result<Challenge, rand::Error> {
+        Challenge::issue(server_key, resource, self.record(key), ttl)
+    }
+
+    /// The number of clients currently tracked.
+    pub fn len(&self) -> usize {
+        self.lock().counts.len()
+    }
+
+    /// Whether no clients are currently tracked.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn cost_at(&self, key: &K, now: Instant) -> u32 {
+        let clients = self.lock();
+        match clients.counts.get(key) {
+            Some(count) => self.cost_for_count(self.decay(*count, now)),
+            None if clients.counts.len() >= self.max_clients => self.max_cost,
+            None => self.min_cost,
+        }
+    }
+
+    fn record_at(&self, key: &K, now: Instant) -> u32 {
+        let mut clients = self.lock();
+        if clients.counts.len() >= clients.next_prune && now >= clients.prune_after {
+            clients
+                .counts
+                .retain(|_, count| self.decay(*count, now) >= NEGLIGIBLE);
+            let len = clients.counts.len();
+            clients.next_prune = (2 * len).max(MIN_PRUNE).min(self.max_clients);
+            // Nobody else can be let in until some clients have gone idle,
+            // which takes at least a `half_life`.
+            if len >= self.max_clients {
+                clients.prune_after = now + self.half_life;
+            }
+        }
+        if !clients.counts.contains_key(key) && clients.counts.len() >= self.max_clients {
+            return self.max_cost;
+        }
+        let count = clients.counts.entry(key.clone()).or_insert(Count {
+            value: 0.0,
+            at: now,
+        });
+        let cost = self.cost_for_count(self.decay(*count, now));
+        *count = Count {
+            value: self.decay(*count, now) + 1.0,
+            at: now,
+        };
+        cost
+    }
+
+    fn decay(&self, count: Count, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(count.at).as_secs_f64();
+        count.value * 0.5f64.powf(elapsed / self.half_life.as_secs_f64())
+    }
+
+    fn cost_for_count(&self, count: f64) -> u32 {
+        if count <= self.allowance {
+            return self.min_cost;
+        }
+        let extra = (count / self.allowance).log2().ceil();
+        if extra >= (self.max_cost - self.min_cost) as f64 {
+            self.max_cost
+        } else {
+            self.min_cost + extra as u32
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Clients<K>> {
+        self.clients
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// The configuration of a `LoadController`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoadConfig {
+    pub min_cost: u32,
+    pub max_cost: u32,
+    /// The cost demanded before any solutions have been seen.
+    pub initial_cost: u32,
+    /// The number of solutions per second the controller aims for, which must
+    /// be positive.
+    pub target_rate: f64,
+    /// How often the cost is adjusted, which must not be zero.
+    pub interval: Duration,
+    /// The fraction of the full correction applied at each adjustment, more
+    /// than zero and at most one. Smaller values respond more slowly but
+    /// oscillate less.
+    pub damping: f64,
+    /// The most the cost may change by in one adjustment, which must be
+    /// finite and not negative.
+    pub max_step: f64,
+}
+
+impl Default for LoadConfig {
+    fn default() -> LoadConfig {
+        LoadConfig {
+            min_cost: 8,
+            max_cost: 32,
+            initial_cost: 16,
+            target_rate: 10.0,
+            interval: Duration::from_secs(10),
+            damping: 0.25,
+            max_step: 2.0,
+        }
+    }
+}
+
+/// A snapshot of the internal state of a `LoadController`, for monitoring.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoadState {
+    /// The current difficulty, as a fractional cost.
+    pub difficulty: f64,
+    /// The cost currently demanded, which is `difficulty` rounded.
+    pub cost: u32,
+    /// The rate at which solutions arrived during the last interval, in
+    /// solutions per second.
+    pub observed_rate: f64,
+    /// The number of solutions which have arrived during this interval.
+    pub pending: u64,
+    /// When this interval started.
+    pub interval_start: Instant,
+}
+
+/// Adjusts a server-wide `cost` so that solutions arrive at `target_rate`.
+///
+/// At the end of every `interval`, the controller compares the rate at which
+/// solutions arrived with its `target_rate`. Since doubling the rate at which
+/// solutions arrive calls for one more leading zero, the difficulty moves
+/// towards the base 2 logarithm of their ratio, scaled by `damping` and
+/// bounded by `max_step`, much like a blockchain's difficulty retarget.
+///
+/// The controller only counts the solutions it is told about with `record`,
+/// and does not verify them itself, so that each one can be checked against
+/// the cost it was actually issued at and recorded only once.
+///
+/// A single controller may be shared between many threads.
+#[derive(Debug)]
+pub struct LoadController {
+    config: LoadConfig,
+    state: Mutex<LoadState>,
+}
+
+impl LoadController {
+    /// # Panics
+    ///
+    /// If `config` breaks any of the requirements documented on `LoadConfig`.
+    pub fn new(config: LoadConfig) -> LoadController {
+        assert!(
+            config.target_rate > 0.0 && config.target_rate.is_finite(),
+            "target_rate must be positive and finite"
+        );
+        assert!(!config.interval.is_zero(), "interval must not be zero");
+        assert!(
+            config.damping > 0.0 && config.damping <= 1.0,
+            "damping must be in (0, 1]"
+        );
+        assert!(
+            config.max_step >= 0.0 && config.max_step.is_finite(),
+            "max_step must be finite and not negative"
+        );
+        let difficulty = config
+            .initial_cost
+            .clamp(config.min_cost, config.max_cost.max(config.min_cost))
+            as f64;
+        LoadController {
+            config,
+            state: Mutex::new(LoadState {
+                difficulty,
+                cost: difficulty as u32,
+                observed_rate: config.target_rate,
+                pending: 0,
+                interval_start: Instant::now(),
+            }),
+        }
+    }
+
+    /// The `cost` currently demanded.
+    pub fn cost(&self) -> u32 {
+        self.state().cost
+    }
+
+    /// The current difficulty as a `Target`, for use with `verify_target`,
+    /// which follows the load more finely than `cost`.
+    pub fn target(&self) -> Target {
+        Target::from_work(2f64.powf(self.state().difficulty))
+    }
+
+    /// The current internal state of the controller.
+    pub fn state(&self) -> LoadState {
+        self.state_at(Instant::now())
+    }
+
+    /// Records the arrival of a solution.
+    ///
+    /// Only solutions which have been accepted exactly once, such as by
+    /// `ReplayGuard::verify_once` or by `verify_challenge_solution` and a
+    /// `ReplayGuard`, should be recorded, as otherwise a single proof replayed
+    /// many times would count as many arrivals and drive the cost up for
+    /// everyone.
+    pub fn record(&self) {
+        self.record_at(Instant::now())
+    }
+
+    fn state_at(&self, now: Instant) -> LoadState {
+        let mut state = self.lock();
+        self.retarget(&mut state, now);
+        *state
+    }
+
+    fn record_at(&self, now: Instant) {
+        let mut state = self.lock();
+        self.retarget(&mut state, now);
+        state.pending += 1;
+    }
+
+    /// Performs the adjustments for every interval which has ended by `now`.
+    fn retarget(&self, state: &mut LoadState, now: Instant) {
+        let config = &self.config;
+        let elapsed = now.saturating_duration_since(state.interval_start);
+        let intervals = (elapsed.as_secs_f64() / config.interval.as_secs_f64()).floor();
+        if intervals < 1.0 {
+            return;
+        }
+        state.observed_rate = state.pending as f64 / config.interval.as_secs_f64();
+        // No solutions at all call for the largest possible step down, as the
+        // logarithm of a ratio of zero would be negative infinity.
+        let correction = if state.pending == 0 {
+            -config.max_step
+        } else {
+            config.damping * (state.observed_rate / config.target_rate).log2()
+        };
+        // Any further intervals which have ended saw no solutions either.
+        let step = correction.clamp(-config.max_step, config.max_step)
+            - config.max_step * (intervals - 1.0);
+        state.difficulty = (state.difficulty + step).clamp(
+            config.min_cost as f64,
+            config.max_cost.max(config.min_cost) as f64,
+        );
+        state.cost = state.difficulty.round() as u32;
+        state.pending = 0;
+        state.interval_start += config.interval.mul_f64(intervals);
+        if intervals > 1.0 {
+            state.observed_rate = 0.0;
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, LoadState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replay::ReplayGuard;
+
+    #[test]
+    fn cost_rises_and_decays() {
+        let controller = DifficultyController::new(8, 12, Duration::from_secs(60), 4.0);
+        let start = Instant::now();
+        let costs: Vec<u32> = (0..40)
+            .map(|_| controller.record_at(&"abuser", start))
+            .collect();
+        assert_eq!(&costs[..5], &[8, 8, 8, 8, 8]);
+        assert_eq!(&costs[5..9], &[9, 9, 9, 9]);
+        assert_eq!(costs[9], 10);
+        assert_eq!(costs[39], 12);
+        assert_eq!(controller.cost_at(&"bystander", start), 8);
+        let later = start + Duration::from_secs(60);
+        assert_eq!(controller.cost_at(&"abuser", later), 11);
+        let much_later = start + Duration::from_secs(600);
+        assert_eq!(controller.cost_at(&"abuser", much_later), 8);
+    }
+
+    #[test]
+    fn idle_clients_are_pruned() {
+        let controller = DifficultyController::new(8, 12, Duration::from_secs(1), 4.0);
+        let start = Instant::now();
+        for client in 0..MIN_PRUNE {
+            controller.record_at(&client, start);
+        }
+        assert_eq!(controller.len(), MIN_PRUNE);
+        controller.record_at(&MIN_PRUNE, start + Duration::from_secs(60));
+        assert_eq!(controller.len(), 1);
+    }
+
+    #[test]
+    fn challenges_keep_the_issued_cost() -> Result<(), rand::Error> {
+        let controller = DifficultyController::new(4, 30, Duration::from_secs(60), 1.0);
+        let key = [7; blake3::KEY_LEN];
+        let ttl = Duration::from_secs(60);
+        let first = controller.issue_challenge(&"client", &key, b"/login", ttl)?;
+        let second = controller.issue_challenge(&"client", &key, b"/login", ttl)?;
+        let third = controller.issue_challenge(&"client", &key, b"/login", ttl)?;
+        assert_eq!((first.cost(), second.cost(), third.cost()), (4, 4, 5));
+        assert_eq!(controller.cost(&"client"), 6);
+        Ok(())
+    }
+
+    #[test]
+    fn clients_are_bounded() {
+        let controller =
+            DifficultyController::new(8, 12, Duration::from_secs(60), 4.0).max_clients(3);
+        let start = Instant::now();
+        for client in 0..3 {
+            assert_eq!(controller.record_at(&client, start), 8);
+        }
+        // Full of active clients, so a new one pays the most and is not
+        // tracked, while existing ones carry on as before.
+        assert_eq!(controller.cost_at(&3, start), 12);
+        assert_eq!(controller.record_at(&3, start), 12);
+        assert_eq!(controller.record_at(&0, start), 8);
+        assert_eq!(controller.len(), 3);
+        // Once they have gone idle, there is room again.
+        let later = start + Duration::from_secs(3600);
+        assert_eq!(controller.record_at(&3, later), 8);
+        assert_eq!(controller.len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "half_life")]
+    fn controller_rejects_zero_half_life() {
+        DifficultyController::<u32>::new(8, 12, Duration::ZERO, 4.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "allowance")]
+    fn controller_rejects_non_positive_allowance() {
+        DifficultyController::<u32>::new(8, 12, Duration::from_secs(60), 0.0);
+    }
+
+    #[test]
+    fn load_controller_retargets() {
+        let controller = LoadController::new(LoadConfig {
+            target_rate: 1.0,
+            interval: Duration::from_secs(10),
+            damping: 0.5,
+            max_step: 2.0,
+            ..LoadConfig::default()
+        });
+        let start = controller.state().interval_start;
+        assert_eq!(controller.cost(), 16);
+        // Four times the target rate calls for two more leading zeros, of
+        // which half is applied.
+        for _ in 0..40 {
+            controller.record_at(start);
+        }
+        let state = controller.state_at(start + Duration::from_secs(10));
+        assert_eq!(state.observed_rate, 4.0);
+        assert_eq!(state.difficulty, 17.0);
+        assert_eq!(state.cost, 17);
+        assert_eq!(state.pending, 0);
+        // A flood is only answered at the maximum step.
+        for _ in 0..100000 {
+            controller.record_at(start + Duration::from_secs(10));
+        }
+        assert_eq!(
+            controller
+                .state_at(start + Duration::from_secs(20))
+                .difficulty,
+            19.0
+        );
+        // Idle intervals step down, but never below the minimum.
+        let state = controller.state_at(start + Duration::from_secs(50));
+        assert_eq!(state.difficulty, 13.0);
+        assert_eq!(state.observed_rate, 0.0);
+        let state = controller.state_at(start + Duration::from_secs(1000));
+        assert_eq!(state.difficulty, 8.0);
+        assert!(controller.target() > Target::from_cost(9));
+    }
+
+    #[test]
+    fn replayed_solutions_are_recorded_once() {
+        let controller = LoadController::new(LoadConfig {
+            target_rate: 1.0,
+            interval: Duration::from_secs(10),
+            ..LoadConfig::default()
+        });
+        let guard = ReplayGuard::new(Duration::from_secs(60), 16);
+        let start = controller.state().interval_start;
+        let bytes = b"replayed";
+        let nonce = crate::search(bytes, 16, u32::MAX).unwrap();
+        for _ in 0..1000 {
+            if guard.verify_once(bytes, nonce, controller.cost()).is_ok() {
+                controller.record_at(start);
+            }
+        }
+        assert_eq!(controller.state_at(start).pending, 1);
+        let state = controller.state_at(start + Duration::from_secs(10));
+        assert!(state.difficulty < 16.0);
+    }
+
+    #[test]
+    fn load_controller_survives_idle_intervals() {
+        let controller = LoadController::new(LoadConfig {
+            damping: f64::MIN_POSITIVE,
+            interval: Duration::from_nanos(1),
+            ..LoadConfig::default()
+        });
+        let start = controller.state().interval_start;
+        let state = controller.state_at(start + Duration::from_secs(1));
+        assert_eq!(state.difficulty, 8.0);
+        assert_eq!(state.cost, 8);
+    }
+
+    #[test]
+    #[should_panic(expected = "damping")]
+    fn load_controller_rejects_zero_damping() {
+        LoadController::new(LoadConfig {
+            damping: 0.0,
+            ..LoadConfig::default()
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "interval")]
+    fn load_controller_rejects_zero_interval() {
+        LoadController::new(LoadConfig {
+            interval: Duration::ZERO,
+            ..LoadConfig::default()
+        });
+    }
+}
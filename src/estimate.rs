@@ -0,0 +1,143 @@
+//! Estimates of how much work a search needs.
+//!
+//! Each attempt of a search at some `cost` succeeds independently with
+//! probability `2^-cost`, so the number of attempts a search needs follows a
+//! geometric distribution. Note that `search` with a `meter` of `m` makes up
+//! to `m + 1` attempts, which the functions here take into account.
+
+use std::time::Duration;
+
+/// The probability that a single attempt at `cost` succeeds.
+fn attempt_probability(cost: u32) -> f64 {
+    2f64.powi(-(cost.min(i32::MAX as u32) as i32))
+}
+
+/// The number of attempts needed to succeed with at least `probability`.
+fn attempts_for_probability(cost: u32, probability: f64) -> f64 {
+    if probability <= 0.0 {
+        return 0.0;
+    }
+    if probability >= 1.0 {
+        return f64::INFINITY;
+    }
+    let p = attempt_probability(cost);
+    if p >= 1.0 {
+        return 1.0;
+    }
+    let mut attempts = ((-probability).ln_1p() / (-p).ln_1p()).ceil().max(1.0);
+    if attempts > u32::MAX as f64 {
+        return attempts;
+    }
+    // Correct for rounding, so that this agrees exactly with
+    // `success_probability`.
+    while within(p, attempts) < probability {
+        attempts += 1.0;
+    }
+    while attempts > 1.0 && within(p, attempts - 1.0) >= probability {
+        attempts -= 1.0;
+    }
+    attempts
+}
+
+/// The probability of succeeding within `attempts` when each succeeds with
+/// probability `p`.
+fn within(p: f64, attempts: f64) -> f64 {
+    -(attempts * (-p).ln_1p()).exp_m1()
+}
+
+/// The expected number of attempts a search at `cost` needs, `2^cost`.
+pub fn expected_attempts(cost: u32) -> f64 {
+    1.0 / attempt_probability(cost)
+}
+
+/// The probability that `search` at `cost` succeeds before overdrawing
+/// `meter`.
+pub fn success_probability(cost: u32, meter: u32) -> f64 {
+    within(attempt_probability(cost), meter as f64 + 1.0)
+}
+
+/// The smallest `meter` for which `search` at `cost` succeeds with at least
+/// the given `probability`, or `u32::MAX` if no `meter` is large enough.
+///
+/// For example, a search with `meter_for_probability(cost, 1.0 - 1e-6)` fails
+/// less than one time in a million.
+pub fn meter_for_probability(cost: u32, probability: f64) -> u32 {
+    let attempts = attempts_for_probability(cost, probability);
+    if attempts > u32::MAX as f64 {
+        u32::MAX
+    } else {
+        (attempts as u32).saturating_sub(1)
+    }
+}
+
+/// How long it takes for a search at `cost` to have succeeded with the given
+/// `probability`, trying `hash_rate` `nonce`s per second. For instance, with
+/// a `probability` of `0.99`, this is the 99th percentile latency.
+pub fn latency_percentile(cost: u32, hash_rate: f64, probability: f64) -> Duration {
+    let seconds = attempts_for_probability(cost, probability) / hash_rate;
+    if seconds.is_finite() && seconds < Duration::MAX.as_secs_f64() {
+        Duration::from_secs_f64(seconds)
+    } else {
+        Duration::MAX
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{search, Error};
+
+    #[test]
+    fn estimates_are_consistent() {
+        assert_eq!(expected_attempts(0), 1.0);
+        assert_eq!(expected_attempts(20), (1 << 20) as f64);
+        assert_eq!(success_probability(0, 0), 1.0);
+        assert_eq!(success_probability(1, 0), 0.5);
+        assert_eq!(success_probability(1, 1), 0.75);
+        for cost in [1, 4, 12, 20, 24] {
+            for probability in [0.5, 0.9, 1.0 - 1e-6] {
+                let meter = meter_for_probability(cost, probability);
+                assert!(success_probability(cost, meter) >= probability);
+                assert!(meter == 0 || success_probability(cost, meter - 1) < probability);
+            }
+        }
+        assert_eq!(meter_for_probability(30, 1.0 - 1e-6), u32::MAX);
+        assert_eq!(meter_for_probability(200, 0.5), u32::MAX);
+        assert_eq!(latency_percentile(0, 1000.0, 0.5), Duration::from_millis(1));
+        assert!(latency_percentile(10, 1000.0, 0.99) > latency_percentile(10, 1000.0, 0.5));
+        assert_eq!(latency_percentile(10, 1000.0, 1.0), Duration::MAX);
+    }
+
+    #[test]
+    fn estimates_match_search() {
+        let cost = 4;
+        let trials = 4000;
+        let bytes = b"124124125124214121";
+        for meter in [0, 7, 15, 40] {
+            let mut successes = 0;
+            for _ in 0..trials {
+                match search(bytes, cost, meter) {
+                    Ok(_) => successes += 1,
+                    Err(Error::MeterOverdrawn) => {}
+                    Err(e) => panic!("{}", e),
+                }
+            }
+            let expected = success_probability(cost, meter);
+            let observed = successes as f64 / trials as f64;
+            // Five standard deviations either way.
+            let tolerance = 5.0 * (expected * (1.0 - expected) / trials as f64).sqrt();
+            assert!(
+                (observed - expected).abs() <= tolerance.max(1e-9),
+                "meter {}: observed {}, expected {}",
+                meter,
+                observed,
+                expected
+            );
+        }
+        // With every search failing at most one time in a trillion, this
+        // fails far less often than the tolerances above.
+        let meter = meter_for_probability(cost, 1.0 - 1e-12);
+        assert!(1.0 - success_probability(cost, meter) <= 1e-12);
+        assert!((0..trials).all(|_| search(bytes, cost, meter).is_ok()));
+    }
+}
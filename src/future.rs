@@ -0,0 +1,188 @@
+//! Searches which cooperate with asynchronous executors.
+
+use crate::limits::{scaled_interval, Budget};
+use crate::{search_batch, Error, Nonces, SearchLimits, Strategy, NONCE_SIZE};
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// The most attempts an asynchronous search makes before yielding. This must
+/// be a power of two.
+const BATCH: u32 = 1024;
+
+/// Roughly how many bytes an asynchronous search hashes before yielding, so
+/// that searches over large inputs do not hold up the executor for long.
+const BATCH_BYTES: usize = 1 << 18;
+
+/// # Asynchronous proof search
+///
+/// Performs the same search as `search_limited`, but yields to the executor
+/// after every batch of attempts so that other tasks can make progress. This
+/// works with any executor, but the hashing still happens on the task polling
+/// the future, so on a multi-threaded runtime it may be preferable to use
+/// `spawn_search` instead.
+pub async fn search_async(
+    bytes: &[u8],
+    cost: u32,
+    limits: &SearchLimits,
+) -> Result<[u8; NONCE_SIZE], Error> {
+    let mut budget = Budget::new(limits, bytes.len());
+    let mut nonces = Nonces::new(Strategy::Random)?;
+    let batch = scaled_interval(bytes.len(), BATCH_BYTES, BATCH);
+    loop {
+        if let Some(nonce) = search_batch(bytes, cost, &mut budget, &mut nonces, batch)? {
+            return Ok(nonce);
+        }
+        YieldNow(false).await;
+    }
+}
+
+/// A future which is pending the first time it is polled, having asked to be
+/// woken straight away, and ready the second time.
+struct YieldNow(bool);
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.0 {
+            return Poll::Ready(());
+        }
+        self.0 = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+/// # Proof search on the tokio blocking pool
+///
+/// Performs the same search as `search_limited` on a thread from tokio's
+/// blocking pool, so that it does not hold up the runtime's workers. If the
+/// returned future is dropped before it completes, the search is cancelled.
+#[cfg(feature = "tokio")]
+pub async fn spawn_search(
+    bytes: Vec<u8>,
+    cost: u32,
+    limits: SearchLimits,
+) -> Result<[u8; NONCE_SIZE], Error> {
+    let cancel = match &limits.cancel {
+        Some(cancel) => cancel.child(),
+        None => crate::CancelToken::new(),
+    };
+    let guard = CancelOnDrop(cancel.clone());
+    let limits = SearchLimits {
+        cancel: Some(cancel),
+        ..limits
+    };
+    let result =
+        tokio::task::spawn_blocking(move || crate::search_limited(&bytes, cost, &limits)).await;
+    drop(guard);
+    match result {
+        Ok(result) => result,
+        Err(error) if error.is_panic() => std::panic::resume_unwind(error.into_panic()),
+        Err(_) => Err(Error::Cancelled),
+    }
+}
+
+/// Cancels a token when dropped.
+#[cfg(feature = "tokio")]
+struct CancelOnDrop(crate::CancelToken);
+
+#[cfg(feature = "tokio")]
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.cancel();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::task::{Wake, Waker};
+
+    /// Counts how many times it was woken.
+    struct CountingWaker(AtomicUsize);
+
+    impl Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Polls `future` until it is ready, returning its output and how many
+    /// times it yielded.
+    fn block_on<F: Future>(future: F) -> (F::Output, usize) {
+        let waker = Arc::new(CountingWaker(AtomicUsize::new(0)));
+        let counted = Waker::from(waker.clone());
+        let mut cx = Context::from_waker(&counted);
+        let mut future = std::pin::pin!(future);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return (output, waker.0.load(Ordering::Relaxed));
+            }
+        }
+    }
+
+    #[test]
+    fn search_async_works() -> Result<(), Error> {
+        let bytes = b"124124125124214121";
+        let (nonce, _) = block_on(search_async(bytes, 12, &SearchLimits::default()));
+        assert!(crate::verify(bytes, nonce?, 12));
+        Ok(())
+    }
+
+    #[test]
+    fn search_async_yields() {
+        let bytes = b"124124125124214121";
+        let limits = SearchLimits::attempts(10 * BATCH);
+        let (result, yields) = block_on(search_async(bytes, 200, &limits));
+        assert!(matches!(result, Err(Error::MeterOverdrawn)));
+        assert_eq!(yields, 10);
+    }
+
+    #[test]
+    fn search_async_yields_often_on_large_inputs() {
+        let bytes = vec![7; BATCH_BYTES / 16];
+        let limits = SearchLimits::attempts(BATCH);
+        let (result, yields) = block_on(search_async(&bytes, 200, &limits));
+        assert!(matches!(result, Err(Error::MeterOverdrawn)));
+        assert_eq!(yields, 64);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn spawn_search_works() -> Result<(), Error> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let bytes = b"124124125124214121";
+        let nonce = runtime.block_on(spawn_search(bytes.to_vec(), 12, SearchLimits::default()))?;
+        assert!(crate::verify(bytes, nonce, 12));
+        Ok(())
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn spawn_search_cancels_on_drop() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        let parent = crate::CancelToken::new();
+        let limits = SearchLimits::cancellable(parent.clone());
+        runtime.block_on(async {
+            let search = spawn_search(b"124124125124214121".to_vec(), 200, limits);
+            let timeout = tokio::time::sleep(std::time::Duration::from_millis(20));
+            tokio::select! {
+                _ = search => panic!("search should not finish"),
+                _ = timeout => {}
+            }
+        });
+        assert!(!parent.is_cancelled());
+        // Shutting the runtime down waits for the blocking search, which only
+        // finishes because it was cancelled.
+        drop(runtime);
+    }
+}
@@ -0,0 +1,471 @@
+//! Hashcash stamps, enabled by the `hashcash` feature.
+//!
+//! A version 1 [Hashcash](http://www.hashcash.org/) stamp has the form
+//! `1:bits:date:resource:ext:rand:counter`, and is a proof of work if the
+//! hash of the whole stamp has at least `bits` leading zeros. Stamps hashed
+//! with SHA-1 interoperate with existing Hashcash tools and mail filters,
+//! while stamps hashed with Blake3 use the same hash function as the rest of
+//! this crate.
+
+use crate::leading_zeros;
+use base64::engine::general_purpose::STANDARD_NO_PAD;
+use base64::Engine;
+use sha1::{Digest, Sha1};
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The number of random bytes in a freshly minted stamp.
+const RAND_SIZE: usize = 12usize;
+
+/// How far in the future a stamp's date may be, to allow for clock skew.
+const FUTURE_GRACE: Duration = Duration::from_secs(2 * 24 * 60 * 60);
+
+/// Errors which can occur in parsing or verifying a stamp.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    Malformed,
+    Version,
+    WrongResource,
+    InsufficientBits,
+    Expired,
+    FutureDate,
+    InsufficientWork,
+}
+
+/// Errors which can occur in minting a stamp.
+#[derive(Debug)]
+pub enum MintError {
+    /// The resource contains a `:`, so it cannot be part of a stamp.
+    InvalidResource,
+    Search(crate::Error),
+}
+
+/// The hash function a stamp is checked with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Algorithm {
+    /// SHA-1, as used by standard Hashcash.
+    #[default]
+    Sha1,
+    Blake3,
+}
+
+impl Algorithm {
+    /// The number of leading zeros of the hash of `bytes`.
+    pub fn leading_zeros(self, bytes: &[u8]) -> u32 {
+        match self {
+            Algorithm::Sha1 => leading_zeros(&Sha1::digest(bytes)),
+            Algorithm::Blake3 => leading_zeros(blake3::hash(bytes).as_bytes()),
+        }
+    }
+}
+
+/// A version 1 Hashcash stamp.
+///
+/// The fields are kept exactly as they appear in the stamp, since the stamp
+/// is hashed as text.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Stamp {
+    pub bits: u32,
+    /// The date the stamp was minted, as `YYMMDD`, `YYMMDDhhmm` or
+    /// `YYMMDDhhmmss` in UTC.
+    pub date: String,
+    pub resource: String,
+    pub ext: String,
+    pub rand: String,
+    pub counter: String,
+}
+
+impl Stamp {
+    /// When this stamp was minted, if its date is valid.
+    pub fn time(&self) -> Option<SystemTime> {
+        parse_date(&self.date).map(|seconds| UNIX_EPOCH + Duration::from_secs(seconds))
+    }
+
+    /// The number of leading zeros of the hash of this stamp.
+    pub fn measure(&self, algorithm: Algorithm) -> u32 {
+        algorithm.leading_zeros(self.to_string().as_bytes())
+    }
+}
+
+impl fmt::Display for Stamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "1:{}:{}:{}:{}:{}:{}",
+            self.bits, self.date, self.resource, self.ext, self.rand, self.counter
+        )
+    }
+}
+
+impl FromStr for Stamp {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Stamp, Error> {
+        let fields: Vec<&str> = s.split(':').collect();
+        if fields.first() != Some(&"1") {
+            return Err(if fields.len() > 1 {
+                Error::Version
+            } else {
+                Error::Malformed
+            });
+        }
+        let [_, bits, date, resource, ext, rand, counter] = fields[..] else {
+            return Err(Error::Malformed);
+        };
+        if !bits.bytes().all(|b| b.is_ascii_digit()) || parse_date(date).is_none() {
+            return Err(Error::Malformed);
+        }
+        Ok(Stamp {
+            bits: bits.parse().map_err(|_| Error::Malformed)?,
+            date: date.to_string(),
+            resource: resource.to_string(),
+            ext: ext.to_string(),
+            rand: rand.to_string(),
+            counter: counter.to_string(),
+        })
+    }
+}
+
+/// # Stamp minting
+///
+/// Mints a stamp for `resource` with at least `bits` leading zeros under
+/// `algorithm`, dated today, giving up after `meter` attempts just as `search`
+/// does. Fails with `MintError::InvalidResource` if `resource` contains a
+/// `:`.
+pub fn mint(
+    resource: &str,
+    bits: u32,
+    algorithm: Algorithm,
+    meter: u32,
+) -> Result<Stamp, MintError> {
+    mint_at(resource, bits, algorithm, SystemTime::now(), meter)
+}
+
+/// # Stamp minting at a given time
+///
+/// Performs the same search as `mint`, dating the stamp at `now`.
+pub fn mint_at(
+    resource: &str,
+    bits: u32,
+    algorithm: Algorithm,
+    now: SystemTime,
+    meter: u32,
+) -> Result<Stamp, MintError> {
+    use rand::Fill;
+    if resource.contains(':') {
+        return Err(MintError::InvalidResource);
+    }
+    let mut rand = [0u8; RAND_SIZE];
+    rand.try_fill(&mut rand::thread_rng())
+        .map_err(crate::Error::from)?;
+    let mut stamp = Stamp {
+        bits,
+        date: format_date(now),
+        resource: resource.to_string(),
+        ext: String::new(),
+        rand: STANDARD_NO_PAD.encode(rand),
+        counter: String::new(),
+    };
+    let prefix = stamp.to_string();
+    let mut text = prefix.clone();
+    let mut counter = 0u64;
+    loop {
+        let encoded =
+            STANDARD_NO_PAD.encode(&counter.to_be_bytes()[counter.leading_zeros() as usize / 8..]);
+        text.truncate(prefix.len());
+        text.push_str(&encoded);
+        if algorithm.leading_zeros(text.as_bytes()) >= bits {
+            stamp.counter = encoded;
+            return Ok(stamp);
+        }
+        counter += 1;
+        if counter > u64::from(meter) {
+            return Err(MintError::Search(crate::Error::MeterOverdrawn));
+        }
+    }
+}
+
+/// # Stamp verification
+///
+/// Parses `stamp` and checks that it is for `resource`, claims at least
+/// `bits`, is no older than `max_age` and, hashed with `algorithm`, has as
+/// many leading zeros as it claims. A `max_age` too large to represent means
+/// that stamps never expire.
+pub fn verify_stamp(
+    stamp: &str,
+    resource: &str,
+    bits: u32,
+    algorithm: Algorithm,
+    max_age: Duration,
+) -> Result<Stamp, Error> {
+    verify_stamp_at(stamp, resource, bits, algorithm, max_age, SystemTime::now())
+}
+
+/// # Stamp verification at a given time
+///
+/// Performs the same checks as `verify_stamp`, treating `now` as the current
+/// time.
+pub fn verify_stamp_at(
+    stamp: &str,
+    resource: &str,
+    bits: u32,
+    algorithm: Algorithm,
+    max_age: Duration,
+    now: SystemTime,
+) -> Result<Stamp, Error> {
+    let parsed: Stamp = stamp.parse()?;
+    if parsed.resource != resource {
+        return Err(Error::WrongResource);
+    }
+    if parsed.bits < bits {
+        return Err(Error::InsufficientBits);
+    }
+    let time = parsed.time().ok_or(Error::Malformed)?;
+    if now
+        .checked_add(FUTURE_GRACE)
+        .is_some_and(|latest| time > latest)
+    {
+        return Err(Error::FutureDate);
+    }
+    if time.checked_add(max_age).is_some_and(|expiry| expiry < now) {
+        return Err(Error::Expired);
+    }
+    if algorithm.leading_zeros(stamp.as_bytes()) < parsed.bits {
+        return Err(Error::InsufficientWork);
+    }
+    Ok(parsed)
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Malformed => write!(f, "stamp is malformed"),
+            Error::Version => write!(f, "stamp is not version 1"),
+            Error::WrongResource => write!(f, "stamp is for a different resource"),
+            Error::InsufficientBits => write!(f, "stamp claims too few bits"),
+            Error::Expired => write!(f, "stamp has expired"),
+            Error::FutureDate => write!(f, "stamp is dated in the future"),
+            Error::InsufficientWork => write!(f, "stamp has fewer leading zeros than it claims"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl fmt::Display for MintError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MintError::InvalidResource => write!(f, "resource contains a ':'"),
+            MintError::Search(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for MintError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MintError::InvalidResource => None,
+            MintError::Search(e) => Some(e),
+        }
+    }
+}
+
+impl From<crate::Error> for MintError {
+    fn from(e: crate::Error) -> MintError {
+        MintError::Search(e)
+    }
+}
+
+/// Formats `time` as `YYMMDD` in UTC.
+fn format_date(time: SystemTime) -> String {
+    let seconds = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let (year, month, day) = civil_from_days((seconds / 86400) as i64);
+    format!("{:02}{:02}{:02}", year.rem_euclid(100), month, day)
+}
+
+/// Parses `YYMMDD`, `YYMMDDhhmm` or `YYMMDDhhmmss` into seconds since the
+/// Unix epoch. Two-digit years from 70 onwards are in the twentieth century.
+fn parse_date(date: &str) -> Option<u64> {
+    if !matches!(date.len(), 6 | 10 | 12) || !date.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let field = |i: usize| {
+        date.get(i..i + 2)
+            .map_or(Some(0), |f| f.parse::<u64>().ok())
+    };
+    let (year, month, day) = (field(0)?, field(2)?, field(4)?);
+    let (hour, minute, second) = (field(6)?, field(8)?, field(10)?);
+    let year = if year < 70 { 2000 + year } else { 1900 + year } as i64;
+    let days = days_from_civil(year, month as u32, day as u32);
+    if civil_from_days(days) != (year, month as u32, day as u32)
+        || hour > 23
+        || minute > 59
+        || second > 59
+    {
+        return None;
+    }
+    Some(days as u64 * 86400 + hour * 3600 + minute * 60 + second)
+}
+
+/// The number of days since the Unix epoch of a date in the proleptic
+/// Gregorian calendar.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = month as i64;
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// The date in the proleptic Gregorian calendar a number of days after the
+/// Unix epoch.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: Duration = Duration::from_secs(86400);
+
+    #[test]
+    fn dates_round_trip() {
+        assert_eq!(parse_date("700101"), Some(0));
+        assert_eq!(parse_date("000229"), Some(951782400));
+        assert_eq!(parse_date("0402291234"), Some(1078058040));
+        assert_eq!(parse_date("040229123456"), Some(1078058096));
+        assert_eq!(parse_date("010229"), None);
+        assert_eq!(parse_date("0402291260"), None);
+        assert_eq!(parse_date("04022"), None);
+        for seconds in [0, 951782400, 1078058040, 3144268799] {
+            let time = UNIX_EPOCH + Duration::from_secs(seconds);
+            assert_eq!(
+                parse_date(&format_date(time)),
+                Some(seconds - seconds % 86400)
+            );
+        }
+    }
+
+    #[test]
+    fn verifies_legacy_stamps() {
+        // The example stamp from the Hashcash website.
+        let stamp = "1:20:060408:adam@cypherspace.org::1QTjaYd7niiQA/sc:ePa";
+        let now = UNIX_EPOCH + Duration::from_secs(parse_date("060409").unwrap());
+        let parsed = verify_stamp_at(
+            stamp,
+            "adam@cypherspace.org",
+            20,
+            Algorithm::Sha1,
+            28 * DAY,
+            now,
+        )
+        .unwrap();
+        assert_eq!(parsed.to_string(), stamp);
+        assert_eq!(parsed.rand, "1QTjaYd7niiQA/sc");
+        assert_eq!(
+            verify_stamp_at(stamp, "eve@example.com", 20, Algorithm::Sha1, 28 * DAY, now),
+            Err(Error::WrongResource)
+        );
+        assert_eq!(
+            verify_stamp_at(
+                stamp,
+                "adam@cypherspace.org",
+                21,
+                Algorithm::Sha1,
+                28 * DAY,
+                now
+            ),
+            Err(Error::InsufficientBits)
+        );
+        assert_eq!(
+            verify_stamp_at(
+                stamp,
+                "adam@cypherspace.org",
+                20,
+                Algorithm::Sha1,
+                28 * DAY,
+                now + 30 * DAY
+            ),
+            Err(Error::Expired)
+        );
+        assert_eq!(
+            verify_stamp_at(
+                stamp,
+                "adam@cypherspace.org",
+                20,
+                Algorithm::Sha1,
+                28 * DAY,
+                now - 4 * DAY
+            ),
+            Err(Error::FutureDate)
+        );
+    }
+
+    #[test]
+    fn parsing_is_strict() {
+        assert_eq!("0:20:060408:a::b:c".parse::<Stamp>(), Err(Error::Version));
+        assert_eq!("1:20:060408:a::b".parse::<Stamp>(), Err(Error::Malformed));
+        assert_eq!(
+            "1:20:060408:a::b:c:d".parse::<Stamp>(),
+            Err(Error::Malformed)
+        );
+        assert_eq!(
+            "1:+20:060408:a::b:c".parse::<Stamp>(),
+            Err(Error::Malformed)
+        );
+        assert_eq!("1:20:061308:a::b:c".parse::<Stamp>(), Err(Error::Malformed));
+        assert_eq!("".parse::<Stamp>(), Err(Error::Malformed));
+    }
+
+    #[test]
+    fn minted_stamps_verify() -> Result<(), MintError> {
+        for algorithm in [Algorithm::Sha1, Algorithm::Blake3] {
+            let stamp = mint("alice@example.com", 12, algorithm, 100000000)?;
+            let text = stamp.to_string();
+            assert!(stamp.measure(algorithm) >= 12);
+            assert_eq!(
+                verify_stamp(&text, "alice@example.com", 12, algorithm, DAY),
+                Ok(stamp)
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn edge_cases_are_errors() -> Result<(), MintError> {
+        assert!(matches!(
+            mint("a:b", 0, Algorithm::Sha1, 10),
+            Err(MintError::InvalidResource)
+        ));
+        let stamp = mint("alice@example.com", 0, Algorithm::Sha1, 10)?.to_string();
+        assert!(verify_stamp(
+            &stamp,
+            "alice@example.com",
+            0,
+            Algorithm::Sha1,
+            Duration::MAX
+        )
+        .is_ok());
+        assert_eq!(Error::Expired.to_string(), "stamp has expired");
+        Ok(())
+    }
+}
@@ -8,21 +8,107 @@
 //! This crate provides functionality for `search`ing and `verify`ing this
 //! sort of proof of work.
 
+use std::sync::atomic::{AtomicBool, Ordering};
+
+#[cfg(feature = "http")]
+pub mod binding;
+mod calibrate;
+pub mod challenge;
+#[cfg(feature = "http")]
+pub mod client;
+mod context;
+pub mod difficulty;
+mod estimate;
+mod future;
+#[cfg(feature = "hashcash")]
+pub mod hashcash;
+mod limits;
+#[cfg(feature = "tower")]
+pub mod middleware;
+mod progress;
+pub mod proof;
+pub mod replay;
+mod scheme;
+#[cfg(feature = "serde")]
+pub mod serde_support;
+mod stream;
+mod target;
+
+pub use calibrate::{bench, calibrate, calibrate_target, hash_rate, Benchmark};
+pub use context::Context;
+pub use estimate::{
+    expected_attempts, latency_percentile, meter_for_probability, success_probability,
+};
+pub use future::search_async;
+#[cfg(feature = "tokio")]
+pub use future::spawn_search;
+pub use limits::{CancelToken, SearchLimits};
+pub use progress::Progress;
+pub use scheme::Scheme;
+#[cfg(feature = "mmap")]
+pub use stream::{search_file, verify_file};
+pub use stream::{search_reader, verify_reader};
+pub use target::Target;
+
+use limits::Budget;
+use progress::Tracker;
+use scheme::SchemeHasher;
+
 pub const NONCE_SIZE: usize = 10usize;
 
+/// The most worker threads a parallel search starts, however many it is
+/// asked for.
+const MAX_THREADS: u64 = 1024;
+
 /// Errors which can occur in searching for a proof of work.
 #[derive(Debug)]
 pub enum Error {
     Rand(rand::Error),
     MeterOverdrawn,
+    Timeout,
+    Cancelled,
+    Io(std::io::Error),
 }
 
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Rand(error) => write!(f, "random number generation failed: {}", error),
+            Error::MeterOverdrawn => write!(f, "meter overdrawn before a proof was found"),
+            Error::Timeout => write!(f, "timed out before a proof was found"),
+            Error::Cancelled => write!(f, "search cancelled"),
+            Error::Io(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
 impl From<rand::Error> for Error {
     fn from(error: rand::Error) -> Error {
         Error::Rand(error)
     }
 }
 
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Error {
+        Error::Io(error)
+    }
+}
+
+/// How a search chooses the `nonce`s it tries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Strategy {
+    /// Draw a fresh random `nonce` for every attempt.
+    #[default]
+    Random,
+    /// Draw a random starting `nonce` once and then count upwards from it,
+    /// treating the `nonce` as a little-endian integer. No `nonce` is ever
+    /// tried twice within a single search.
+    Counter,
+}
+
 /// # Proof search
 ///
 /// Searches through random `nonce`s by guessing random length `NONCE_SIZE`
@@ -34,27 +120,382 @@ impl From<rand::Error> for Error {
 /// If we search through `meter` `nonce`s, we return an `Error::MeterOverdrawn`
 /// error.
 pub fn search(bytes: &[u8], cost: u32, meter: u32) -> Result<[u8; NONCE_SIZE], Error> {
-    use rand::Fill;
-    let mut rng = rand::thread_rng();
-    let mut nonce = [0u8; NONCE_SIZE];
+    search_with(bytes, cost, meter, Strategy::Random)
+}
+
+/// # Proof search with a chosen strategy
+///
+/// Performs the same search as `search`, but picks the `nonce`s to try
+/// according to the given `strategy`.
+pub fn search_with(
+    bytes: &[u8],
+    cost: u32,
+    meter: u32,
+    strategy: Strategy,
+) -> Result<[u8; NONCE_SIZE], Error> {
+    search_by(meter, strategy, |nonce| measure(bytes, *nonce) >= cost)
+}
+
+/// # Proof search under a scheme
+///
+/// Performs the same search as `search`, but lays out the input to the hash
+/// function according to the given `scheme`.
+pub fn search_scheme(
+    bytes: &[u8],
+    cost: u32,
+    meter: u32,
+    scheme: Scheme,
+) -> Result<[u8; NONCE_SIZE], Error> {
+    let hasher = SchemeHasher::new(scheme, bytes);
+    search_by(meter, Strategy::Random, |nonce| {
+        leading_zeros(hasher.hash(nonce).as_bytes()) >= cost
+    })
+}
+
+/// # Proof search over a digest
+///
+/// Performs the same search as `search_scheme` with `Scheme::Prehashed`, but
+/// takes the Blake3 `digest` of the `bytes` rather than the `bytes`
+/// themselves, as computed by `blake3::hash`.
+pub fn search_digest(
+    digest: &[u8; blake3::OUT_LEN],
+    cost: u32,
+    meter: u32,
+) -> Result<[u8; NONCE_SIZE], Error> {
+    search(digest, cost, meter)
+}
+
+/// # Proof search against a target
+///
+/// Performs the same search as `search`, but looks for a `nonce` such that
+/// the hash of the `nonce` appended to `bytes` meets the given `target`. This
+/// allows for difficulties in between those expressible as a `cost`.
+pub fn search_target(bytes: &[u8], target: Target, meter: u32) -> Result<[u8; NONCE_SIZE], Error> {
+    search_by(meter, Strategy::Random, |nonce| {
+        verify_target(bytes, *nonce, target)
+    })
+}
+
+/// # Proof search in a context
+///
+/// Performs the same search as `search`, but hashes in the given `context`,
+/// so that the proof is only valid for `verify_in` with the same `context`.
+pub fn search_in(
+    context: &Context,
+    bytes: &[u8],
+    cost: u32,
+    meter: u32,
+) -> Result<[u8; NONCE_SIZE], Error> {
+    search_by(meter, Strategy::Random, |nonce| {
+        leading_zeros(context.hash(bytes, nonce).as_bytes()) >= cost
+    })
+}
+
+/// # Proof search within limits
+///
+/// Performs the same search as `search`, but stops according to the given
+/// `limits`. If we try `limits.max_attempts` `nonce`s, we return an
+/// `Error::MeterOverdrawn` error, and if we run out of time, we return an
+/// `Error::Timeout` error. If the search is cancelled through
+/// `limits.cancel`, we return an `Error::Cancelled` error. With no limits set,
+/// the search runs until it succeeds.
+pub fn search_limited(
+    bytes: &[u8],
+    cost: u32,
+    limits: &SearchLimits,
+) -> Result<[u8; NONCE_SIZE], Error> {
+    let mut budget = Budget::new(limits, bytes.len());
+    let mut nonces = Nonces::new(Strategy::Random)?;
+    loop {
+        if let Some(nonce) = search_batch(bytes, cost, &mut budget, &mut nonces, u32::MAX)? {
+            return Ok(nonce);
+        }
+    }
+}
+
+/// Tries up to `batch` `nonce`s from `nonces`, stopping early if `budget`
+/// runs out.
+pub(crate) fn search_batch(
+    bytes: &[u8],
+    cost: u32,
+    budget: &mut Budget,
+    nonces: &mut Nonces,
+    batch: u32,
+) -> Result<Option<[u8; NONCE_SIZE]>, Error> {
+    for _ in 0..batch {
+        budget.spend()?;
+        let nonce = nonces.next()?;
+        if measure(bytes, nonce) >= cost {
+            return Ok(Some(nonce));
+        }
+    }
+    Ok(None)
+}
+
+/// # Proof search with progress reports
+///
+/// Performs the same search as `search_limited`, but calls `report` with the
+/// `Progress` of the search every `interval` attempts.
+pub fn search_with_progress(
+    bytes: &[u8],
+    cost: u32,
+    limits: &SearchLimits,
+    interval: u32,
+    mut report: impl FnMut(&Progress),
+) -> Result<[u8; NONCE_SIZE], Error> {
+    let interval = interval.max(1);
+    let mut until_report = interval;
+    let mut budget = Budget::new(limits, bytes.len());
+    let mut tracker = Tracker::new(cost);
+    let mut nonces = Nonces::new(Strategy::Random)?;
+    loop {
+        budget.spend()?;
+        let nonce = nonces.next()?;
+        let achieved_cost = measure(bytes, nonce);
+        if achieved_cost >= cost {
+            return Ok(nonce);
+        }
+        tracker.record(achieved_cost);
+        until_report -= 1;
+        if until_report == 0 {
+            report(&tracker.progress());
+            until_report = interval;
+        }
+    }
+}
+
+/// A `nonce` together with the `cost` it achieves, as found by `search_best`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Solution {
+    #[cfg_attr(feature = "serde", serde(with = "serde_support::array"))]
+    pub nonce: [u8; NONCE_SIZE],
+    /// The number of leading zeros of the hash of `nonce` appended to the
+    /// `bytes`.
+    pub achieved_cost: u32,
+}
+
+/// # Best-effort proof search
+///
+/// Performs the same search as `search`, but keeps track of the `nonce` with
+/// the most leading zeros seen so far. Rather than returning an
+/// `Error::MeterOverdrawn` error when `meter` runs out, we return that best
+/// `nonce`, and it is left to the verifier to decide whether the `cost` it
+/// achieved is good enough.
+pub fn search_best(bytes: &[u8], cost: u32, meter: u32) -> Result<Solution, Error> {
+    let mut nonces = Nonces::new(Strategy::Random)?;
+    let nonce = nonces.next()?;
+    let mut best = Solution {
+        nonce,
+        achieved_cost: measure(bytes, nonce),
+    };
+    let mut counter = 0;
+    while best.achieved_cost < cost && counter < meter {
+        let nonce = nonces.next()?;
+        let achieved_cost = measure(bytes, nonce);
+        if achieved_cost > best.achieved_cost {
+            best = Solution {
+                nonce,
+                achieved_cost,
+            };
+        }
+        counter += 1;
+    }
+    Ok(best)
+}
+
+/// Searches for a `nonce` accepted by `accept`.
+fn search_by(
+    meter: u32,
+    strategy: Strategy,
+    accept: impl Fn(&[u8; NONCE_SIZE]) -> bool,
+) -> Result<[u8; NONCE_SIZE], Error> {
+    let mut nonces = Nonces::new(strategy)?;
     let mut counter = 0;
     loop {
-        nonce.try_fill(&mut rng)?;
-        let mut hasher = blake3::Hasher::new();
-        hasher.update(&nonce);
-        hasher.update(bytes);
-        let hash = hasher.finalize();
-        if leading_zeros(hash.as_bytes()) >= cost {
-            break;
+        let nonce = nonces.next()?;
+        if accept(&nonce) {
+            return Ok(nonce);
         }
         counter += 1;
         if counter > meter {
             return Err(Error::MeterOverdrawn);
         }
     }
+}
+
+/// # Parallel proof search
+///
+/// Performs the same search as `search`, but fans the work out over `threads`
+/// worker threads. If `threads` is zero, one worker is started per logical CPU
+/// as reported by `num_cpus`. No more workers are started than there are
+/// attempts to make, nor than 1024. As soon as any worker finds a valid
+/// `nonce`, all of the others stop.
+///
+/// Just as with `search`, up to `meter + 1` `nonce`s are tried before we
+/// return an `Error::MeterOverdrawn` error. These attempts are split between
+/// the workers.
+pub fn par_search(
+    bytes: &[u8],
+    cost: u32,
+    meter: u32,
+    threads: usize,
+) -> Result<[u8; NONCE_SIZE], Error> {
+    par_search_with(bytes, cost, meter, threads, Strategy::Random)
+}
+
+/// # Parallel proof search with a chosen strategy
+///
+/// Performs the same search as `par_search`, but picks the `nonce`s to try
+/// according to the given `strategy`. With `Strategy::Counter`, each worker
+/// counts through its own disjoint range of `nonce`s.
+pub fn par_search_with(
+    bytes: &[u8],
+    cost: u32,
+    meter: u32,
+    threads: usize,
+    strategy: Strategy,
+) -> Result<[u8; NONCE_SIZE], Error> {
+    par_search_by(meter, threads, strategy, &|nonce| {
+        measure(bytes, *nonce) >= cost
+    })
+}
+
+/// Spreads a search for a `nonce` accepted by `accept` over `threads` workers.
+fn par_search_by<F>(
+    meter: u32,
+    threads: usize,
+    strategy: Strategy,
+    accept: &F,
+) -> Result<[u8; NONCE_SIZE], Error>
+where
+    F: Fn(&[u8; NONCE_SIZE]) -> bool + Sync,
+{
+    let threads = if threads == 0 {
+        num_cpus::get()
+    } else {
+        threads
+    };
+    // As many attempts as `search` makes, which may not fit in a `u32`.
+    let attempts = u64::from(meter) + 1;
+    // Every worker makes at least one attempt.
+    let threads = (threads as u64).min(attempts).min(MAX_THREADS);
+    let start = Nonces::new(strategy)?;
+    let stop = AtomicBool::new(false);
+    let results: Vec<Result<Option<[u8; NONCE_SIZE]>, Error>> = std::thread::scope(|scope| {
+        let mut offset = 0u64;
+        let mut workers = Vec::new();
+        for i in 0..threads {
+            let share = attempts / threads + u64::from(i < attempts % threads);
+            // The offset of any worker is less than `attempts`, so it fits.
+            let nonces = start.skip(offset as u32);
+            let stop = &stop;
+            workers.push(scope.spawn(move || search_worker(share, nonces, accept, stop)));
+            offset += share;
+        }
+        workers
+            .into_iter()
+            .map(|worker| worker.join().expect("search worker panicked"))
+            .collect()
+    });
+    let mut error = None;
+    for result in results {
+        match result {
+            Ok(Some(nonce)) => return Ok(nonce),
+            Ok(None) => {}
+            Err(e) => error = Some(e),
+        }
+    }
+    Err(error.unwrap_or(Error::MeterOverdrawn))
+}
+
+/// Tries up to `share` `nonce`s, giving up early once `stop` is set by
+/// another worker. Sets `stop` itself when it finishes with a result that
+/// should end the whole search.
+fn search_worker<F>(
+    share: u64,
+    mut nonces: Nonces,
+    accept: &F,
+    stop: &AtomicBool,
+) -> Result<Option<[u8; NONCE_SIZE]>, Error>
+where
+    F: Fn(&[u8; NONCE_SIZE]) -> bool,
+{
+    for _ in 0..share {
+        if stop.load(Ordering::Relaxed) {
+            return Ok(None);
+        }
+        let nonce = match nonces.next() {
+            Ok(nonce) => nonce,
+            Err(e) => {
+                stop.store(true, Ordering::Relaxed);
+                return Err(e);
+            }
+        };
+        if accept(&nonce) {
+            stop.store(true, Ordering::Relaxed);
+            return Ok(Some(nonce));
+        }
+    }
+    Ok(None)
+}
+
+/// The sequence of `nonce`s tried by a search using some `Strategy`.
+#[derive(Clone, Copy)]
+pub(crate) enum Nonces {
+    Random,
+    Counter([u8; NONCE_SIZE]),
+}
+
+impl Nonces {
+    pub(crate) fn new(strategy: Strategy) -> Result<Nonces, Error> {
+        Ok(match strategy {
+            Strategy::Random => Nonces::Random,
+            Strategy::Counter => Nonces::Counter(random_nonce()?),
+        })
+    }
+
+    /// The same sequence, advanced past its first `n` elements.
+    fn skip(mut self, n: u32) -> Nonces {
+        if let Nonces::Counter(next) = &mut self {
+            add(next, n);
+        }
+        self
+    }
+
+    fn next(&mut self) -> Result<[u8; NONCE_SIZE], Error> {
+        match self {
+            Nonces::Random => random_nonce(),
+            Nonces::Counter(next) => {
+                let nonce = *next;
+                add(next, 1);
+                Ok(nonce)
+            }
+        }
+    }
+}
+
+fn random_nonce() -> Result<[u8; NONCE_SIZE], Error> {
+    use rand::Fill;
+    let mut nonce = [0u8; NONCE_SIZE];
+    nonce.try_fill(&mut rand::thread_rng())?;
     Ok(nonce)
 }
 
+/// Adds `n` to `nonce`, treating it as a little-endian integer and wrapping
+/// around on overflow.
+fn add(nonce: &mut [u8; NONCE_SIZE], n: u32) {
+    let mut carry = n as u64;
+    for byte in nonce.iter_mut() {
+        if carry == 0 {
+            break;
+        }
+        let sum = *byte as u64 + (carry & 0xff);
+        *byte = sum as u8;
+        carry = (carry >> 8) + (sum >> 8);
+    }
+}
+
 /// # Proof verification
 ///
 /// This checks that the hash of the `nonce` appended to the `bytes` has
@@ -62,11 +503,84 @@ pub fn search(bytes: &[u8], cost: u32, meter: u32) -> Result<[u8; NONCE_SIZE], E
 /// wheher or not this nonce constitutes a valid proof of work for this cost
 /// and input.
 pub fn verify(bytes: &[u8], nonce: [u8; NONCE_SIZE], cost: u32) -> bool {
+    measure(bytes, nonce) >= cost
+}
+
+/// The outcome of checking a proof of work with `verify_detailed`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Verification {
+    /// The Blake3 hash of the `nonce` appended to the `bytes`.
+    pub hash: blake3::Hash,
+    /// The number of leading zeros of `hash`, which is the highest `cost` the
+    /// proof is valid for.
+    pub achieved_cost: u32,
+    /// Whether `achieved_cost` meets the requested `cost`.
+    pub valid: bool,
+}
+
+/// # Proof measurement
+///
+/// Computes the number of leading zeros of the hash of the `nonce` appended
+/// to the `bytes`. In other words, this is the highest `cost` for which the
+/// `nonce` constitutes a valid proof of work for this input.
+pub fn measure(bytes: &[u8], nonce: [u8; NONCE_SIZE]) -> u32 {
+    leading_zeros(hash(bytes, &nonce).as_bytes())
+}
+
+/// # Detailed proof verification
+///
+/// Performs the same check as `verify`, but also reports the hash which was
+/// checked and the `cost` the proof actually achieved.
+pub fn verify_detailed(bytes: &[u8], nonce: [u8; NONCE_SIZE], cost: u32) -> Verification {
+    let hash = hash(bytes, &nonce);
+    let achieved_cost = leading_zeros(hash.as_bytes());
+    Verification {
+        hash,
+        achieved_cost,
+        valid: achieved_cost >= cost,
+    }
+}
+
+/// # Proof verification under a scheme
+///
+/// This checks that the hash of the `nonce` and the `bytes`, laid out
+/// according to the given `scheme`, has `cost` or more leading zeros.
+pub fn verify_scheme(bytes: &[u8], nonce: [u8; NONCE_SIZE], cost: u32, scheme: Scheme) -> bool {
+    leading_zeros(SchemeHasher::new(scheme, bytes).hash(&nonce).as_bytes()) >= cost
+}
+
+/// # Proof verification over a digest
+///
+/// Performs the same check as `verify_scheme` with `Scheme::Prehashed`, but
+/// takes the Blake3 `digest` of the `bytes` rather than the `bytes`
+/// themselves. A server can hash a large input once and then check proofs
+/// over it cheaply.
+pub fn verify_digest(digest: &[u8; blake3::OUT_LEN], nonce: [u8; NONCE_SIZE], cost: u32) -> bool {
+    verify(digest, nonce, cost)
+}
+
+/// # Proof verification against a target
+///
+/// This checks that the hash of the `nonce` appended to the `bytes` meets
+/// the given `target`.
+pub fn verify_target(bytes: &[u8], nonce: [u8; NONCE_SIZE], target: Target) -> bool {
+    target.is_met_by(hash(bytes, &nonce).as_bytes())
+}
+
+/// # Proof verification in a context
+///
+/// This checks that the hash of the `nonce` appended to the `bytes` in the
+/// given `context` has `cost` or more leading zeros.
+pub fn verify_in(context: &Context, bytes: &[u8], nonce: [u8; NONCE_SIZE], cost: u32) -> bool {
+    leading_zeros(context.hash(bytes, &nonce).as_bytes()) >= cost
+}
+
+/// The Blake3 hash of `nonce` followed by `bytes`.
+pub(crate) fn hash(bytes: &[u8], nonce: &[u8; NONCE_SIZE]) -> blake3::Hash {
     let mut hasher = blake3::Hasher::new();
-    hasher.update(&nonce);
+    hasher.update(nonce);
     hasher.update(bytes);
-    let hash = hasher.finalize();
-    leading_zeros(hash.as_bytes()) >= cost
+    hasher.finalize()
 }
 
 /// Compute the number of leading zeros of the given byte array.
@@ -74,7 +588,7 @@ pub fn leading_zeros(bytes: &[u8]) -> u32 {
     let mut count = 0;
     let mut ptr = bytes;
     loop {
-        if ptr.len() == 0 {
+        if ptr.is_empty() {
             break;
         } else {
             let lz = ptr[0].leading_zeros();
@@ -121,4 +635,227 @@ mod tests {
         }
         Ok(())
     }
+
+    #[test]
+    fn add_carries() {
+        let mut nonce = [0xff, 0xff, 0, 0, 0, 0, 0, 0, 0, 0];
+        add(&mut nonce, 1);
+        assert_eq!(nonce, [0, 0, 1, 0, 0, 0, 0, 0, 0, 0]);
+        add(&mut nonce, 0x1_0203);
+        assert_eq!(nonce, [3, 2, 2, 0, 0, 0, 0, 0, 0, 0]);
+        let mut nonce = [0xff; NONCE_SIZE];
+        add(&mut nonce, 2);
+        assert_eq!(nonce, [1, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn counter_strategy_works() -> Result<(), Error> {
+        let cost = 16;
+        let meter = 100000000;
+        let bytes = b"124124125124214121";
+        let nonce = search_with(bytes, cost, meter, Strategy::Counter)?;
+        assert!(verify(bytes, nonce, cost));
+        let nonce = par_search_with(bytes, cost, meter, 4, Strategy::Counter)?;
+        assert!(verify(bytes, nonce, cost));
+        Ok(())
+    }
+
+    #[test]
+    fn counter_strategy_never_repeats() -> Result<(), Error> {
+        let mut nonces = Nonces::new(Strategy::Counter)?;
+        let mut seen = std::collections::HashSet::new();
+        for _ in 0..100000 {
+            assert!(seen.insert(nonces.next()?));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn search_target_works() -> Result<(), Error> {
+        let meter = 100000000;
+        let bytes = b"124124125124214121";
+        let target = Target::from_work(1.2 * (1 << 16) as f64);
+        let nonce = search_target(bytes, target, meter)?;
+        assert!(verify_target(bytes, nonce, target));
+        Ok(())
+    }
+
+    #[test]
+    fn verify_detailed_works() -> Result<(), Error> {
+        let bytes = b"124124125124214121";
+        let nonce = search(bytes, 12, 100000000)?;
+        let achieved_cost = measure(bytes, nonce);
+        assert!(achieved_cost >= 12);
+        let verification = verify_detailed(bytes, nonce, 12);
+        assert!(verification.valid);
+        assert_eq!(verification.achieved_cost, achieved_cost);
+        assert_eq!(verification.hash, hash(bytes, &nonce));
+        assert!(!verify_detailed(bytes, nonce, achieved_cost + 1).valid);
+        Ok(())
+    }
+
+    #[test]
+    fn search_best_works() -> Result<(), Error> {
+        let bytes = b"124124125124214121";
+        let best = search_best(bytes, 200, 1000)?;
+        assert!(best.achieved_cost < 200);
+        assert_eq!(measure(bytes, best.nonce), best.achieved_cost);
+        let best = search_best(bytes, 12, 100000000)?;
+        assert!(best.achieved_cost >= 12);
+        assert!(verify(bytes, best.nonce, 12));
+        Ok(())
+    }
+
+    #[test]
+    fn search_limited_works() -> Result<(), Error> {
+        let bytes = b"124124125124214121";
+        let nonce = search_limited(bytes, 12, &SearchLimits::default())?;
+        assert!(verify(bytes, nonce, 12));
+        assert!(matches!(
+            search_limited(bytes, 200, &SearchLimits::attempts(1000)),
+            Err(Error::MeterOverdrawn)
+        ));
+        assert!(matches!(
+            search_limited(bytes, 200, &SearchLimits::attempts(0)),
+            Err(Error::MeterOverdrawn)
+        ));
+        let start = std::time::Instant::now();
+        let duration = std::time::Duration::from_millis(50);
+        assert!(matches!(
+            search_limited(bytes, 200, &SearchLimits::duration(duration)),
+            Err(Error::Timeout)
+        ));
+        assert!(start.elapsed() >= duration);
+        assert!(matches!(
+            search_limited(bytes, 200, &SearchLimits::deadline(start)),
+            Err(Error::Timeout)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn search_limited_can_be_cancelled() {
+        let bytes = b"124124125124214121";
+        let token = CancelToken::new();
+        let limits = SearchLimits::cancellable(token.clone());
+        std::thread::scope(|scope| {
+            let search = scope.spawn(|| search_limited(bytes, 200, &limits));
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            token.cancel();
+            assert!(matches!(search.join().unwrap(), Err(Error::Cancelled)));
+        });
+    }
+
+    #[test]
+    fn search_with_progress_reports() {
+        let bytes = b"124124125124214121";
+        let mut reports = Vec::new();
+        let result =
+            search_with_progress(bytes, 200, &SearchLimits::attempts(1000), 100, |progress| {
+                reports.push(*progress)
+            });
+        assert!(matches!(result, Err(Error::MeterOverdrawn)));
+        assert_eq!(reports.len(), 10);
+        for (i, progress) in reports.iter().enumerate() {
+            assert_eq!(progress.attempts, 100 * (i as u64 + 1));
+            assert!(progress.hash_rate > 0.0);
+            assert_eq!(progress.expected_attempts, 2f64.powi(200));
+        }
+        assert!(reports.windows(2).all(|w| w[0].best_cost <= w[1].best_cost));
+        assert!(reports[9].best_cost > 0);
+    }
+
+    #[test]
+    fn search_scheme_works() -> Result<(), Error> {
+        let bytes = [7u8; 1 << 16];
+        for scheme in Scheme::ALL {
+            let nonce = search_scheme(&bytes, 8, 100000000, scheme)?;
+            assert!(verify_scheme(&bytes, nonce, 8, scheme));
+            assert_eq!(
+                verify_scheme(&bytes, nonce, 8, Scheme::NonceFirst),
+                verify(&bytes, nonce, 8)
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn search_digest_works() -> Result<(), Error> {
+        let bytes = [7u8; 1 << 16];
+        let digest = blake3::hash(&bytes).into();
+        let nonce = search_digest(&digest, 12, 100000000)?;
+        assert!(verify_digest(&digest, nonce, 12));
+        assert!(verify_scheme(&bytes, nonce, 12, Scheme::Prehashed));
+        Ok(())
+    }
+
+    #[test]
+    fn par_search_works() -> Result<(), Error> {
+        let cost = 20;
+        let meter = 100000000;
+        let bytes = b"124124125124214121";
+        for threads in 0..5 {
+            let nonce = par_search(bytes, cost, meter, threads)?;
+            assert!(verify(bytes, nonce, cost));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn par_search_overdraws_meter() {
+        let bytes = b"124124125124214121";
+        assert!(matches!(
+            par_search(bytes, 200, 1000, 4),
+            Err(Error::MeterOverdrawn)
+        ));
+        // Like `search`, a `meter` of zero still allows for one attempt.
+        assert!(par_search(bytes, 0, 0, 4).is_ok());
+        assert!(search(bytes, 0, 0).is_ok());
+        // Absurd numbers of threads are no more than there are attempts.
+        assert!(par_search(bytes, 0, 0, usize::MAX).is_ok());
+        assert!(matches!(
+            par_search(bytes, 200, 1000, usize::MAX),
+            Err(Error::MeterOverdrawn)
+        ));
+    }
+
+    #[test]
+    fn context_test_vectors() {
+        let bytes = b"124124125124214121";
+        let a = Context::new("proof-of-work test vectors, service A");
+        let b = Context::new("proof-of-work test vectors, service B");
+        let zero = [0; NONCE_SIZE];
+        assert_eq!(
+            a.hash(bytes, &zero).to_hex().as_str(),
+            "032d4eb50c4bb1506dcc0342bb8e37b9f19081283b7634f2ebb968d3523d8989"
+        );
+        assert_eq!(
+            b.hash(bytes, &zero).to_hex().as_str(),
+            "a4f91f947d48a81dbde6930c7af530ec0df890e44cabf6e54f7b0828dde2f8c7"
+        );
+        let mut nonce_a = [0; NONCE_SIZE];
+        nonce_a[..2].copy_from_slice(&[86, 25]);
+        let mut nonce_b = [0; NONCE_SIZE];
+        nonce_b[..2].copy_from_slice(&[176, 15]);
+        assert!(verify_in(&a, bytes, nonce_a, 12));
+        assert!(verify_in(&b, bytes, nonce_b, 12));
+        assert!(!verify_in(&a, bytes, nonce_b, 12));
+        assert!(!verify_in(&b, bytes, nonce_a, 12));
+        assert!(!verify(bytes, nonce_a, 12));
+        assert!(!verify(bytes, nonce_b, 12));
+    }
+
+    #[test]
+    fn search_in_is_bound_to_its_context() -> Result<(), Error> {
+        let bytes = b"124124125124214121";
+        let a = Context::new("proof-of-work test vectors, service A");
+        let b = Context::new("proof-of-work test vectors, service B");
+        let nonce = search_in(&a, bytes, 16, 100000000)?;
+        assert!(verify_in(&a, bytes, nonce, 16));
+        assert!(!verify_in(&b, bytes, nonce, 16));
+        assert!(!verify(bytes, nonce, 16));
+        assert_eq!(a.as_str(), "proof-of-work test vectors, service A");
+        assert_ne!(a, b);
+        Ok(())
+    }
 }
@@ -0,0 +1,245 @@
+//! Bounds on how long a search may run for.
+
+use crate::Error;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// The most attempts a search makes between looks at the clock and at its
+/// `CancelToken`. This must be a power of two.
+const CHECK_INTERVAL: u32 = 256;
+
+/// Roughly how many bytes a search hashes between looks at the clock and at
+/// its `CancelToken`, so that searches over large inputs still look often.
+const CHECK_BYTES: usize = 1 << 16;
+
+/// A handle with which a search can be stopped from another thread.
+///
+/// Clones of a token share their state, so cancelling any of them stops every
+/// search which was given one of them.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<Flag>);
+
+#[derive(Debug, Default)]
+struct Flag {
+    cancelled: AtomicBool,
+    parent: Option<CancelToken>,
+}
+
+impl CancelToken {
+    pub fn new() -> CancelToken {
+        CancelToken::default()
+    }
+
+    /// A new token which is cancelled whenever this one is, but which can
+    /// also be cancelled on its own without affecting this one.
+    pub fn child(&self) -> CancelToken {
+        CancelToken(Arc::new(Flag {
+            cancelled: AtomicBool::new(false),
+            parent: Some(self.clone()),
+        }))
+    }
+
+    /// Asks every search using this token to stop with `Error::Cancelled`.
+    pub fn cancel(&self) {
+        self.0.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether `cancel` has been called on this token, any of its clones, or
+    /// the token it is a child of.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.cancelled.load(Ordering::Relaxed)
+            || self
+                .0
+                .parent
+                .as_ref()
+                .is_some_and(CancelToken::is_cancelled)
+    }
+}
+
+/// Limits on the work done by `search_limited`. Each limit is optional, and a
+/// search stops as soon as any of them is reached or it is cancelled.
+///
+/// Attempt counts are a poor proxy for latency when hash rates vary widely
+/// between machines, so a search may also be bounded by a `max_duration`
+/// measured from when it starts, or by a fixed `deadline`.
+#[derive(Debug, Clone, Default)]
+pub struct SearchLimits {
+    /// The most `nonce`s to try before giving up with `Error::MeterOverdrawn`.
+    pub max_attempts: Option<u32>,
+    /// How long to search for before giving up with `Error::Timeout`.
+    pub max_duration: Option<Duration>,
+    /// When to give up with `Error::Timeout`.
+    pub deadline: Option<Instant>,
+    /// A token with which to stop the search early with `Error::Cancelled`.
+    pub cancel: Option<CancelToken>,
+}
+
+impl SearchLimits {
+    /// Limits allowing at most `max_attempts` attempts.
+    pub fn attempts(max_attempts: u32) -> SearchLimits {
+        SearchLimits {
+            max_attempts: Some(max_attempts),
+            ..SearchLimits::default()
+        }
+    }
+
+    /// Limits allowing a search to run for at most `max_duration`.
+    pub fn duration(max_duration: Duration) -> SearchLimits {
+        SearchLimits {
+            max_duration: Some(max_duration),
+            ..SearchLimits::default()
+        }
+    }
+
+    /// Limits requiring a search to finish by `deadline`.
+    pub fn deadline(deadline: Instant) -> SearchLimits {
+        SearchLimits {
+            deadline: Some(deadline),
+            ..SearchLimits::default()
+        }
+    }
+
+    /// Limits allowing a search to be stopped with `cancel`.
+    pub fn cancellable(cancel: CancelToken) -> SearchLimits {
+        SearchLimits {
+            cancel: Some(cancel),
+            ..SearchLimits::default()
+        }
+    }
+
+    /// The earliest instant at which a search starting at `start` must stop.
+    fn deadline_from(&self, start: Instant) -> Option<Instant> {
+        let timeout = self
+            .max_duration
+            .and_then(|duration| start.checked_add(duration));
+        match (timeout, self.deadline) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+}
+
+/// The remaining allowance of a single search under some `SearchLimits`.
+pub(crate) struct Budget {
+    max_attempts: Option<u32>,
+    deadline: Option<Instant>,
+    cancel: Option<CancelToken>,
+    attempts: u32,
+    /// One less than the power of two number of attempts between checks.
+    check_mask: u32,
+}
+
+impl Budget {
+    /// The budget of a search over `len` bytes, which is what each attempt
+    /// hashes.
+    pub(crate) fn new(limits: &SearchLimits, len: usize) -> Budget {
+        Budget {
+            max_attempts: limits.max_attempts,
+            deadline: limits.deadline_from(Instant::now()),
+            cancel: limits.cancel.clone(),
+            attempts: 0,
+            check_mask: check_interval(len) - 1,
+        }
+    }
+
+    /// Accounts for one more attempt, failing if the limits do not allow it.
+    pub(crate) fn spend(&mut self) -> Result<(), Error> {
+        if Some(self.attempts) == self.max_attempts {
+            return Err(Error::MeterOverdrawn);
+        }
+        if self.attempts & self.check_mask == 0 {
+            if self.cancel.as_ref().is_some_and(CancelToken::is_cancelled) {
+                return Err(Error::Cancelled);
+            }
+            if let Some(deadline) = self.deadline {
+                if Instant::now() >= deadline {
+                    return Err(Error::Timeout);
+                }
+            }
+        }
+        self.attempts = self.attempts.wrapping_add(1);
+        Ok(())
+    }
+}
+
+/// The number of attempts between checks in a search over `len` bytes.
+fn check_interval(len: usize) -> u32 {
+    scaled_interval(len, CHECK_BYTES, CHECK_INTERVAL)
+}
+
+/// The largest power of two, up to the power of two `max`, of attempts at a
+/// search over `len` bytes which hash no more than about `bytes` in total,
+/// or one if even a single attempt hashes more.
+pub(crate) fn scaled_interval(len: usize, bytes: usize, max: u32) -> u32 {
+    let attempts = bytes / len.max(1);
+    if attempts == 0 {
+        1
+    } else {
+        1 << attempts.ilog2().min(max.ilog2())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn earliest_deadline_wins() {
+        let start = Instant::now();
+        let limits = SearchLimits {
+            max_duration: Some(Duration::from_secs(5)),
+            deadline: Some(start + Duration::from_secs(3)),
+            ..SearchLimits::default()
+        };
+        assert_eq!(limits.deadline_from(start), limits.deadline);
+        let limits = SearchLimits {
+            deadline: Some(start + Duration::from_secs(7)),
+            ..limits
+        };
+        assert_eq!(
+            limits.deadline_from(start),
+            Some(start + Duration::from_secs(5))
+        );
+        assert_eq!(SearchLimits::default().deadline_from(start), None);
+    }
+
+    #[test]
+    fn cancelled_budget_stops() {
+        let token = CancelToken::new();
+        let mut budget = Budget::new(&SearchLimits::cancellable(token.clone()), 0);
+        for _ in 0..10 * CHECK_INTERVAL {
+            assert!(budget.spend().is_ok());
+        }
+        token.clone().cancel();
+        assert!(token.is_cancelled());
+        assert!((0..CHECK_INTERVAL).any(|_| matches!(budget.spend(), Err(Error::Cancelled))));
+    }
+
+    #[test]
+    fn children_follow_parents() {
+        let parent = CancelToken::new();
+        let child = parent.child();
+        let sibling = parent.child();
+        child.cancel();
+        assert!(!parent.is_cancelled());
+        assert!(!sibling.is_cancelled());
+        parent.cancel();
+        assert!(sibling.is_cancelled());
+    }
+
+    #[test]
+    fn large_inputs_are_checked_often() {
+        assert_eq!(check_interval(0), CHECK_INTERVAL);
+        assert_eq!(check_interval(100), CHECK_INTERVAL);
+        assert_eq!(check_interval(1024), 64);
+        assert_eq!(check_interval(3000), 16);
+        assert_eq!(check_interval(1 << 16), 1);
+        assert_eq!(check_interval(1 << 24), 1);
+        let token = CancelToken::new();
+        let mut budget = Budget::new(&SearchLimits::cancellable(token.clone()), 1 << 16);
+        assert!(budget.spend().is_ok());
+        token.cancel();
+        assert!(matches!(budget.spend(), Err(Error::Cancelled)));
+    }
+}
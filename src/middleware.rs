@@ -0,0 +1,386 @@
+//! Requiring proofs of work on HTTP requests with `tower`.
+//!
+//! Wrapping a service in a `ProofOfWorkLayer` meters its usage without
+//! keeping any per-client state. A request without a proof is rejected with a
+//! fresh `Challenge` in the `CHALLENGE_HEADER`, and the client retries it with
+//! that challenge and a `Proof` over it and the request's `request_binding` in
+//! the `PROOF_HEADER`. Each challenge expires after a short `ttl` and is
+//! accepted only once, so every request accepted costs fresh work.
+//!
+//! | Request                                                  | Response                  |
+//! |----------------------------------------------------------|---------------------------|
+//! | no proof, or a malformed, forged, expired or invalid one | `401 Unauthorized`        |
+//! | a challenge of too little work, or a replayed one        | `429 Too Many Requests`   |
+//! | a body larger than `max_body_size`                       | `413 Payload Too Large`   |
+//! | any proof while the `ReplayGuard` is full                | `503 Service Unavailable` |
+//!
+//! Both `401` and `429` responses carry a fresh challenge, which `401`
+//! responses also give in a `WWW-Authenticate` header with the `ProofOfWork`
+//! scheme.
+
+use crate::binding::{
+    challenge_value, parse_proof, proof_input, request_binding, CHALLENGE_HEADER, PROOF_HEADER,
+};
+use crate::challenge::{verify_challenge, Challenge};
+use crate::replay::{self, ReplayGuard};
+use bytes::Bytes;
+use http::header::{HeaderName, HeaderValue, WWW_AUTHENTICATE};
+use http::{Request, Response, StatusCode};
+use http_body::Body;
+use http_body_util::{BodyExt, Full, LengthLimitError, Limited};
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tower_layer::Layer;
+use tower_service::Service;
+
+/// The default limit on the size of request bodies, one mebibyte.
+pub const DEFAULT_MAX_BODY_SIZE: usize = 1 << 20;
+
+/// How long challenges are valid for by default.
+pub const DEFAULT_TTL: Duration = Duration::from_secs(60);
+
+/// How many accepted challenges the default `ReplayGuard` remembers.
+const DEFAULT_REPLAY_CAPACITY: usize = 1 << 20;
+
+/// A `Layer` requiring a proof of work of at least `cost` on every request.
+///
+/// Challenges are authenticated under a secret `key`, which servers sharing
+/// the load of one service should share. Since the proof covers the body, the
+/// body is read into memory before the request is passed on, and the inner
+/// service receives it as `Full<Bytes>`.
+///
+/// Every accepted challenge is remembered by a `ReplayGuard` until it has
+/// expired. By default this holds up to about a million challenges.
+#[derive(Clone)]
+pub struct ProofOfWorkLayer {
+    key: [u8; blake3::KEY_LEN],
+    cost: u32,
+    ttl: Duration,
+    header: HeaderName,
+    max_body_size: usize,
+    replay_guard: Arc<ReplayGuard>,
+    /// Whether `replay_guard` was given by the user, rather than made to
+    /// match `ttl`.
+    custom_replay_guard: bool,
+}
+
+impl ProofOfWorkLayer {
+    pub fn new(key: [u8; blake3::KEY_LEN], cost: u32) -> ProofOfWorkLayer {
+        ProofOfWorkLayer {
+            key,
+            cost,
+            ttl: DEFAULT_TTL,
+            header: PROOF_HEADER,
+            max_body_size: DEFAULT_MAX_BODY_SIZE,
+            replay_guard: default_replay_guard(DEFAULT_TTL),
+            custom_replay_guard: false,
+        }
+    }
+
+    /// Issues challenges valid for `ttl` rather than `DEFAULT_TTL`.
+    pub fn ttl(mut self, ttl: Duration) -> ProofOfWorkLayer {
+        self.ttl = ttl;
+        if !self.custom_replay_guard {
+            self.replay_guard = default_replay_guard(ttl);
+        }
+        self
+    }
+
+    /// Reads proofs from `header` rather than the `PROOF_HEADER`.
+    pub fn header(mut self, header: HeaderName) -> ProofOfWorkLayer {
+        self.header = header;
+        self
+    }
+
+    /// Rejects requests with bodies larger than `max_body_size` bytes.
+    pub fn max_body_size(mut self, max_body_size: usize) -> ProofOfWorkLayer {
+        self.max_body_size = max_body_size;
+        self
+    }
+
+    /// Remembers accepted challenges with `replay_guard`, which may be shared
+    /// with other layers. Its `ttl` must exceed that of the challenges by at
+    /// least a second, or they may be replayed before they expire.
+    pub fn replay_guard(mut self, replay_guard: Arc<ReplayGuard>) -> ProofOfWorkLayer {
+        self.replay_guard = replay_guard;
+        self.custom_replay_guard = true;
+        self
+    }
+
+    /// A response with the given `code` carrying a fresh challenge.
+    fn reject<B: Default>(&self, code: StatusCode) -> Response<B> {
+        let challenge = match Challenge::issue(&self.key, b"", self.cost, self.ttl) {
+            Ok(challenge) => challenge,
+            Err(_) => return status(StatusCode::INTERNAL_SERVER_ERROR),
+        };
+        let value = challenge_value(&challenge);
+        let mut response = status(code);
+        if code == StatusCode::UNAUTHORIZED {
+            // Base64 is a valid `token68`.
+            let authenticate = format!("ProofOfWork {}", value.to_str().unwrap());
+            response.headers_mut().insert(
+                WWW_AUTHENTICATE,
+                HeaderValue::try_from(authenticate).unwrap(),
+            );
+        }
+        response.headers_mut().insert(CHALLENGE_HEADER, value);
+        response
+    }
+}
+
+/// A guard remembering challenges for as long as they may be valid, allowing
+/// for their expiry being rounded to the second.
+fn default_replay_guard(ttl: Duration) -> Arc<ReplayGuard> {
+    Arc::new(ReplayGuard::new(
+        ttl.saturating_add(Duration::from_secs(1)),
+        DEFAULT_REPLAY_CAPACITY,
+    ))
+}
+
+impl fmt::Debug for ProofOfWorkLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProofOfWorkLayer")
+            .field("cost", &self.cost)
+            .field("ttl", &self.ttl)
+            .field("header", &self.header)
+            .field("max_body_size", &self.max_body_size)
+            .field("replay_guard", &self.replay_guard)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<S> Layer<S> for ProofOfWorkLayer {
+    type Service = ProofOfWork<S>;
+
+    fn layer(&self, inner: S) -> ProofOfWork<S> {
+        ProofOfWork {
+            inner,
+            layer: self.clone(),
+        }
+    }
+}
+
+/// The `Service` produced by a `ProofOfWorkLayer`.
+#[derive(Debug, Clone)]
+pub struct ProofOfWork<S> {
+    inner: S,
+    layer: ProofOfWorkLayer,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for ProofOfWork<S>
+where
+    S: Service<Request<Full<Bytes>>, Response = Response<ResBody>> + Clone + Send + 'static,
+    S::Future: Send,
+    ReqBody: Body + Send + 'static,
+    ReqBody::Data: Send,
+    ReqBody::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+    ResBody: Default,
+{
+    type Response = Response<ResBody>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Response<ResBody>, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), S::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<ReqBody>) -> Self::Future {
+        // The clone may not be ready, so keep the one which is.
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let layer = self.layer.clone();
+        Box::pin(async move {
+            let found = request.headers().get(&layer.header).and_then(parse_proof);
+            let (challenge, proof) = match found {
+                Some(found) => found,
+                None => return Ok(layer.reject(StatusCode::UNAUTHORIZED)),
+            };
+            // Check everything we can before reading the body, so that bogus
+            // proofs cost us as little as possible.
+            if verify_challenge(&layer.key, &challenge, b"").is_err() {
+                return Ok(layer.reject(StatusCode::UNAUTHORIZED));
+            }
+            if challenge.cost() < layer.cost || proof.cost < challenge.cost() {
+                return Ok(layer.reject(StatusCode::TOO_MANY_REQUESTS));
+            }
+            let (parts, body) = request.into_parts();
+            let body = match Limited::new(body, layer.max_body_size).collect().await {
+                Ok(collected) => collected.to_bytes(),
+                Err(e) if e.is::<LengthLimitError>() => {
+                    return Ok(status(StatusCode::PAYLOAD_TOO_LARGE))
+                }
+                Err(_) => return Ok(status(StatusCode::BAD_REQUEST)),
+            };
+            let binding = request_binding(&parts.method, &parts.uri, &body);
+            if !proof.verify(&proof_input(&challenge, &binding), challenge.cost()) {
+                return Ok(layer.reject(StatusCode::UNAUTHORIZED));
+            }
+            let key = blake3::hash(&challenge.to_bytes());
+            match layer.replay_guard.insert(*key.as_bytes()) {
+                Ok(()) => {}
+                Err(replay::Error::Full) => return Ok(status(StatusCode::SERVICE_UNAVAILABLE)),
+                Err(_) => return Ok(layer.reject(StatusCode::TOO_MANY_REQUESTS)),
+            }
+            inner
+                .call(Request::from_parts(parts, Full::new(body)))
+                .await
+        })
+    }
+}
+
+fn status<B: Default>(status: StatusCode) -> Response<B> {
+    let mut response = Response::new(B::default());
+    *response.status_mut() = status;
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binding::{parse_challenge, proof_value};
+    use crate::proof::Proof;
+    use crate::Scheme;
+    use std::convert::Infallible;
+    use std::time::SystemTime;
+
+    const KEY: [u8; blake3::KEY_LEN] = [7; blake3::KEY_LEN];
+
+    #[derive(Clone)]
+    struct Echo;
+
+    impl Service<Request<Full<Bytes>>> for Echo {
+        type Response = Response<Full<Bytes>>;
+        type Error = Infallible;
+        type Future = std::future::Ready<Result<Response<Full<Bytes>>, Infallible>>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, request: Request<Full<Bytes>>) -> Self::Future {
+            std::future::ready(Ok(Response::new(request.into_body())))
+        }
+    }
+
+    fn request(body: &'static [u8], proof: Option<HeaderValue>) -> Request<Full<Bytes>> {
+        let mut builder = Request::post("/submit?id=7");
+        if let Some(proof) = proof {
+            builder = builder.header(PROOF_HEADER, proof);
+        }
+        builder.body(Full::new(Bytes::from_static(body))).unwrap()
+    }
+
+    fn input(challenge: &Challenge, body: &[u8]) -> Vec<u8> {
+        let uri = "/submit?id=7".parse().unwrap();
+        proof_input(challenge, &request_binding(&http::Method::POST, &uri, body))
+    }
+
+    /// Solves `challenge` for a request with `body`, but not by chance for
+    /// one with `other` instead.
+    fn prove(challenge: &Challenge, body: &[u8], other: &[u8]) -> HeaderValue {
+        let (input, other) = (input(challenge, body), input(challenge, other));
+        let proof = loop {
+            let proof = Proof::search(&input, challenge.cost(), u32::MAX, Scheme::default());
+            let proof = proof.unwrap();
+            if !proof.verify(&other, challenge.cost()) {
+                break proof;
+            }
+        };
+        proof_value(challenge, &proof)
+    }
+
+    async fn challenge(service: &mut ProofOfWork<Echo>) -> Challenge {
+        let response = service.call(request(b"", None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let value = &response.headers()[CHALLENGE_HEADER];
+        let authenticate = response.headers()[WWW_AUTHENTICATE].to_str().unwrap();
+        assert_eq!(
+            authenticate,
+            format!("ProofOfWork {}", value.to_str().unwrap())
+        );
+        parse_challenge(value).unwrap()
+    }
+
+    #[tokio::test]
+    async fn layer_requires_bound_fresh_proofs() {
+        let mut service = ProofOfWorkLayer::new(KEY, 8).layer(Echo);
+        let issued = challenge(&mut service).await;
+        assert_eq!(issued.cost(), 8);
+
+        let proof = prove(&issued, b"hello", b"goodbye");
+        let response = service
+            .call(request(b"goodbye", Some(proof.clone())))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = service
+            .call(request(b"hello", Some(proof.clone())))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(&body[..], b"hello");
+
+        let response = service.call(request(b"hello", Some(proof))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(parse_challenge(&response.headers()[CHALLENGE_HEADER]).is_some());
+
+        let ttl = Duration::from_secs(60);
+        let weak = Challenge::issue(&KEY, b"", 2, ttl).unwrap();
+        let proof = prove(&weak, b"hello", b"");
+        let response = service.call(request(b"hello", Some(proof))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        let forged = Challenge::issue(&[8; blake3::KEY_LEN], b"", 8, ttl).unwrap();
+        let proof = prove(&forged, b"hello", b"");
+        let response = service.call(request(b"hello", Some(proof))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let long_ago = SystemTime::now() - Duration::from_secs(120);
+        let expired = Challenge::issue_at(&KEY, b"", 8, long_ago, ttl).unwrap();
+        let proof = prove(&expired, b"hello", b"");
+        let response = service.call(request(b"hello", Some(proof))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn layer_limits_body_size_and_replay_memory() {
+        let mut service = ProofOfWorkLayer::new(KEY, 4)
+            .header(HeaderName::from_static("x-work"))
+            .max_body_size(4)
+            .replay_guard(Arc::new(ReplayGuard::new(DEFAULT_TTL, 1)))
+            .layer(Echo);
+        let issued = challenge(&mut service).await;
+        let mut large = request(b"hello", None);
+        large
+            .headers_mut()
+            .insert("x-work", prove(&issued, b"hello", b"hi"));
+        let response = service.call(large).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        for expected in [StatusCode::OK, StatusCode::SERVICE_UNAVAILABLE] {
+            let issued = challenge(&mut service).await;
+            let mut small = request(b"hi", None);
+            small
+                .headers_mut()
+                .insert("x-work", prove(&issued, b"hi", b"hello"));
+            let response = service.call(small).await.unwrap();
+            assert_eq!(response.status(), expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn client_answers_challenges() -> Result<(), crate::Error> {
+        let mut service = ProofOfWorkLayer::new(KEY, 8).layer(Echo);
+        let response = service.call(request(b"hello", None)).await.unwrap();
+        let unsent = Request::post("/submit?id=7").body(b"hello").unwrap();
+        let proof = crate::client::solve_challenge(&unsent, &response, u32::MAX)?.unwrap();
+        let response = service.call(request(b"hello", Some(proof))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        Ok(())
+    }
+}
@@ -0,0 +1,65 @@
+//! Reports on the progress of long-running searches.
+
+use std::time::{Duration, Instant};
+
+/// A snapshot of a search in progress, as passed to the callback given to
+/// `search_with_progress`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Progress {
+    /// The number of `nonce`s tried so far.
+    pub attempts: u64,
+    /// How long the search has been running for.
+    pub elapsed: Duration,
+    /// The number of `nonce`s tried per second so far.
+    pub hash_rate: f64,
+    /// The most leading zeros of any hash seen so far.
+    pub best_cost: u32,
+    /// The expected number of attempts for the whole search, `2^cost`.
+    pub expected_attempts: f64,
+    /// The expected duration of the whole search at the current `hash_rate`,
+    /// less the time already spent, or zero if the search has run longer than
+    /// expected. Each attempt is independent of those before it, so this is
+    /// only a guide; a search is never closer to succeeding for having run
+    /// for a while.
+    pub estimated_remaining: Duration,
+}
+
+/// Keeps the running totals from which `Progress` reports are made.
+pub(crate) struct Tracker {
+    start: Instant,
+    expected_attempts: f64,
+    attempts: u64,
+    best_cost: u32,
+}
+
+impl Tracker {
+    pub(crate) fn new(cost: u32) -> Tracker {
+        Tracker {
+            start: Instant::now(),
+            expected_attempts: 2f64.powi(cost.min(i32::MAX as u32) as i32),
+            attempts: 0,
+            best_cost: 0,
+        }
+    }
+
+    /// Accounts for an attempt achieving `achieved_cost`.
+    pub(crate) fn record(&mut self, achieved_cost: u32) {
+        self.attempts += 1;
+        self.best_cost = self.best_cost.max(achieved_cost);
+    }
+
+    pub(crate) fn progress(&self) -> Progress {
+        let elapsed = self.start.elapsed();
+        let hash_rate = self.attempts as f64 / elapsed.as_secs_f64().max(f64::MIN_POSITIVE);
+        let expected = Duration::try_from_secs_f64(self.expected_attempts / hash_rate)
+            .unwrap_or(Duration::MAX);
+        Progress {
+            attempts: self.attempts,
+            elapsed,
+            hash_rate,
+            best_cost: self.best_cost,
+            expected_attempts: self.expected_attempts,
+            estimated_remaining: expected.saturating_sub(elapsed),
+        }
+    }
+}
@@ -0,0 +1,179 @@
+//! Self-describing proofs of work.
+//!
+//! A bare `nonce` is only meaningful alongside the `cost` and `Scheme` it was
+//! found for. A `Proof` carries all three, and has a canonical binary encoding
+//! as well as a URL-safe base64 text encoding suitable for HTTP headers.
+
+use crate::{search_scheme, verify_scheme, Scheme, NONCE_SIZE};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use std::fmt;
+use std::str::FromStr;
+
+/// The version of the encoding produced by `Proof::to_bytes`.
+pub const VERSION: u8 = 1u8;
+
+/// The length of the encoding produced by `Proof::to_bytes`.
+pub const ENCODED_SIZE: usize = 1 + 1 + 4 + NONCE_SIZE;
+
+/// Errors which can occur in decoding a proof.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    Base64,
+    Length,
+    Version(u8),
+    Algorithm(u8),
+}
+
+/// A `nonce` together with the `cost` and `Scheme` it was found for.
+///
+/// The encoding consists of the encoding version, the version of the `Scheme`
+/// as the algorithm identifier, the `cost` as a big-endian `u32` and finally
+/// the `nonce`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Proof {
+    pub scheme: Scheme,
+    pub cost: u32,
+    pub nonce: [u8; NONCE_SIZE],
+}
+
+impl Proof {
+    /// Searches for a proof for `bytes` at `cost` under `scheme`, giving up
+    /// after `meter` attempts just as `search` does.
+    pub fn search(
+        bytes: &[u8],
+        cost: u32,
+        meter: u32,
+        scheme: Scheme,
+    ) -> Result<Proof, crate::Error> {
+        Ok(Proof {
+            scheme,
+            cost,
+            nonce: search_scheme(bytes, cost, meter, scheme)?,
+        })
+    }
+
+    /// Checks that this proof is valid for `bytes` at the `cost` it claims,
+    /// and that this meets the `cost` demanded by the verifier.
+    pub fn verify(&self, bytes: &[u8], cost: u32) -> bool {
+        self.cost >= cost && verify_scheme(bytes, self.nonce, self.cost, self.scheme)
+    }
+
+    /// The canonical binary encoding of this proof.
+    pub fn to_bytes(&self) -> [u8; ENCODED_SIZE] {
+        let mut bytes = [0u8; ENCODED_SIZE];
+        bytes[0] = VERSION;
+        bytes[1] = self.scheme.version();
+        bytes[2..6].copy_from_slice(&self.cost.to_be_bytes());
+        bytes[6..].copy_from_slice(&self.nonce);
+        bytes
+    }
+
+    /// Decodes a proof from its canonical binary encoding.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Proof, Error> {
+        if bytes.len() != ENCODED_SIZE {
+            return Err(Error::Length);
+        }
+        if bytes[0] != VERSION {
+            return Err(Error::Version(bytes[0]));
+        }
+        let scheme = Scheme::from_version(bytes[1]).ok_or(Error::Algorithm(bytes[1]))?;
+        Ok(Proof {
+            scheme,
+            cost: u32::from_be_bytes(bytes[2..6].try_into().unwrap()),
+            nonce: bytes[6..].try_into().unwrap(),
+        })
+    }
+}
+
+/// Formats the proof as its canonical encoding in unpadded URL-safe base64.
+impl fmt::Display for Proof {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&URL_SAFE_NO_PAD.encode(self.to_bytes()))
+    }
+}
+
+/// Parses the format produced by `Display`, rejecting padding, whitespace and
+/// any other non-canonical base64.
+impl FromStr for Proof {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Proof, Error> {
+        let bytes = URL_SAFE_NO_PAD.decode(s).map_err(|_| Error::Base64)?;
+        Proof::from_bytes(&bytes)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Base64 => write!(f, "proof is not valid unpadded URL-safe base64"),
+            Error::Length => write!(f, "proof is not {} bytes long", ENCODED_SIZE),
+            Error::Version(version) => write!(f, "unknown proof encoding version {}", version),
+            Error::Algorithm(algorithm) => write!(f, "unknown proof algorithm {}", algorithm),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encoding_round_trips() {
+        let proof = Proof {
+            scheme: Scheme::NonceLast,
+            cost: 22,
+            nonce: [0, 1, 2, 3, 4, 5, 6, 7, 8, 255],
+        };
+        assert_eq!(
+            proof.to_bytes(),
+            [1, 2, 0, 0, 0, 22, 0, 1, 2, 3, 4, 5, 6, 7, 8, 255]
+        );
+        assert_eq!(proof.to_string(), "AQIAAAAWAAECAwQFBgcI_w");
+        assert_eq!("AQIAAAAWAAECAwQFBgcI_w".parse(), Ok(proof));
+    }
+
+    #[test]
+    fn parsing_is_strict() {
+        assert_eq!(
+            "AQIAAAAWAAECAwQFBgcI_w==".parse::<Proof>(),
+            Err(Error::Base64)
+        );
+        assert_eq!(
+            "AQIAAAAWAAECAwQFBgcI/w".parse::<Proof>(),
+            Err(Error::Base64)
+        );
+        assert_eq!(
+            "AQIAAAAWAAECAwQFBgcI_x".parse::<Proof>(),
+            Err(Error::Base64)
+        );
+        assert_eq!(
+            " AQIAAAAWAAECAwQFBgcI_w".parse::<Proof>(),
+            Err(Error::Base64)
+        );
+        assert_eq!("AQIAAAAWAAECAwQFBgcI".parse::<Proof>(), Err(Error::Length));
+        assert_eq!(
+            "AgIAAAAWAAECAwQFBgcI_w".parse::<Proof>(),
+            Err(Error::Version(2))
+        );
+        assert_eq!(
+            "AQkAAAAWAAECAwQFBgcI_w".parse::<Proof>(),
+            Err(Error::Algorithm(9))
+        );
+    }
+
+    #[test]
+    fn proofs_verify() -> Result<(), crate::Error> {
+        let bytes = b"124124125124214121";
+        let proof = Proof::search(bytes, 12, 100000000, Scheme::NonceLast)?;
+        let parsed: Proof = proof.to_string().parse().unwrap();
+        assert!(parsed.verify(bytes, 12));
+        assert!(parsed.verify(bytes, 10));
+        assert!(!parsed.verify(bytes, 13));
+        assert!(!Proof { cost: 40, ..parsed }.verify(bytes, 12));
+        Ok(())
+    }
+}
@@ -0,0 +1,211 @@
+//! Protection against replayed proofs of work.
+//!
+//! A `nonce` which constitutes a valid proof of work for some `bytes` does so
+//! forever, so a server which only calls `verify` will accept the same proof
+//! any number of times. A `ReplayGuard` remembers the proofs it has accepted
+//! for a while and rejects them if they are presented again.
+
+use crate::NONCE_SIZE;
+use std::collections::{HashSet, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Errors which can occur in verifying a proof of work exactly once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    InsufficientWork,
+    Replayed,
+    /// The guard already remembers `capacity` proofs, none of which have
+    /// expired, so it cannot tell whether this one is replayed.
+    Full,
+}
+
+/// A record of recently accepted proofs of work, keyed by the hash which was
+/// checked in verifying them.
+///
+/// Each proof is remembered for `ttl` after it was accepted. At most
+/// `capacity` proofs are remembered at once; beyond that, new proofs are
+/// rejected with `Error::Full` until old ones expire, so `capacity` should
+/// comfortably exceed the number of proofs accepted within any `ttl` window.
+/// A guard made with `evict_oldest` instead forgets the oldest proofs early,
+/// which keeps accepting new proofs during a flood at the price of letting
+/// the forgotten ones be replayed. Either way, proofs should be bound to
+/// something which expires within `ttl`, such as a [`Challenge`].
+///
+/// A single guard may be shared between many threads.
+///
+/// [`Challenge`]: crate::challenge::Challenge
+#[derive(Debug)]
+pub struct ReplayGuard {
+    ttl: Duration,
+    capacity: usize,
+    evict_oldest: bool,
+    seen: Mutex<Seen>,
+}
+
+#[derive(Debug, Default)]
+struct Seen {
+    keys: HashSet<[u8; blake3::OUT_LEN]>,
+    /// The keys in `keys`, in the order they were inserted, which is also the
+    /// order in which they expire.
+    order: VecDeque<([u8; blake3::OUT_LEN], Instant)>,
+}
+
+impl ReplayGuard {
+    /// Constructs a guard remembering up to `capacity` proofs for `ttl` each.
+    pub fn new(ttl: Duration, capacity: usize) -> ReplayGuard {
+        ReplayGuard {
+            ttl,
+            capacity,
+            evict_oldest: false,
+            seen: Mutex::new(Seen::default()),
+        }
+    }
+
+    /// Makes this guard forget the oldest proofs when it is full rather than
+    /// rejecting new ones.
+    pub fn evict_oldest(mut self) -> ReplayGuard {
+        self.evict_oldest = true;
+        self
+    }
+
+    /// # Single-use proof verification
+    ///
+    /// Checks that `nonce` is a valid proof of work for `bytes` at `cost`, just
+    /// as `verify` does, and that it has not been accepted before. If both
+    /// hold, the proof is remembered so that it will be rejected next time.
+    pub fn verify_once(
+        &self,
+        bytes: &[u8],
+        nonce: [u8; NONCE_SIZE],
+        cost: u32,
+    ) -> Result<(), Error> {
+        let hash = crate::hash(bytes, &nonce);
+        if crate::leading_zeros(hash.as_bytes()) < cost {
+            return Err(Error::InsufficientWork);
+        }
+        self.insert(*hash.as_bytes())
+    }
+
+    /// Remembers `key`, failing with `Error::Replayed` if it is already
+    /// remembered or with `Error::Full` if there is no room for it.
+    pub fn insert(&self, key: [u8; blake3::OUT_LEN]) -> Result<(), Error> {
+        self.insert_at(key, Instant::now())
+    }
+
+    /// The number of proofs currently remembered.
+    pub fn len(&self) -> usize {
+        let mut seen = self.lock();
+        self.evict(&mut seen, Instant::now());
+        seen.keys.len()
+    }
+
+    /// Whether no proofs are currently remembered.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn insert_at(&self, key: [u8; blake3::OUT_LEN], now: Instant) -> Result<(), Error> {
+        let mut seen = self.lock();
+        self.evict(&mut seen, now);
+        if seen.keys.contains(&key) {
+            return Err(Error::Replayed);
+        }
+        if seen.order.len() >= self.capacity {
+            if !self.evict_oldest || self.capacity == 0 {
+                return Err(Error::Full);
+            }
+            while seen.order.len() >= self.capacity {
+                let (oldest, _) = seen.order.pop_front().unwrap();
+                seen.keys.remove(&oldest);
+            }
+        }
+        seen.keys.insert(key);
+        seen.order.push_back((key, now));
+        Ok(())
+    }
+
+    fn evict(&self, seen: &mut Seen, now: Instant) {
+        while let Some(&(key, inserted)) = seen.order.front() {
+            if now.saturating_duration_since(inserted) < self.ttl {
+                break;
+            }
+            seen.order.pop_front();
+            seen.keys.remove(&key);
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Seen> {
+        self.seen
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verify_once_rejects_replays() -> Result<(), crate::Error> {
+        let guard = ReplayGuard::new(Duration::from_secs(60), 100);
+        let bytes = b"124124125124214121";
+        let nonce = crate::search(bytes, 8, 100000000)?;
+        assert_eq!(guard.verify_once(bytes, nonce, 8), Ok(()));
+        assert_eq!(guard.verify_once(bytes, nonce, 8), Err(Error::Replayed));
+        assert_eq!(
+            guard.verify_once(b"something else", nonce, 200),
+            Err(Error::InsufficientWork)
+        );
+        assert_eq!(guard.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn entries_expire_and_are_bounded() {
+        let guard = ReplayGuard::new(Duration::from_secs(60), 2);
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        assert_eq!(guard.insert_at([1; 32], at(0)), Ok(()));
+        assert_eq!(guard.insert_at([1; 32], at(59)), Err(Error::Replayed));
+        assert_eq!(guard.insert_at([1; 32], at(60)), Ok(()));
+        assert_eq!(guard.insert_at([2; 32], at(61)), Ok(()));
+        // Full, so rather than forgetting [1; 32] early, refuse [3; 32].
+        assert_eq!(guard.insert_at([3; 32], at(62)), Err(Error::Full));
+        assert_eq!(guard.insert_at([1; 32], at(63)), Err(Error::Replayed));
+        assert_eq!(guard.insert_at([3; 32], at(120)), Ok(()));
+        assert_eq!(guard.insert_at([2; 32], at(120)), Err(Error::Replayed));
+    }
+
+    #[test]
+    fn evict_oldest_forgets_early() {
+        let guard = ReplayGuard::new(Duration::from_secs(60), 2).evict_oldest();
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        assert_eq!(guard.insert_at([1; 32], at(0)), Ok(()));
+        assert_eq!(guard.insert_at([2; 32], at(1)), Ok(()));
+        assert_eq!(guard.insert_at([3; 32], at(2)), Ok(()));
+        assert_eq!(guard.insert_at([1; 32], at(3)), Ok(()));
+        assert_eq!(guard.insert_at([3; 32], at(4)), Err(Error::Replayed));
+        let empty = ReplayGuard::new(Duration::from_secs(60), 0).evict_oldest();
+        assert_eq!(empty.insert([1; 32]), Err(Error::Full));
+    }
+
+    #[test]
+    fn guard_is_shareable() {
+        let guard = ReplayGuard::new(Duration::from_secs(60), 1000);
+        let accepted: usize = std::thread::scope(|scope| {
+            let workers: Vec<_> = (0..8)
+                .map(|_| {
+                    scope.spawn(|| {
+                        (0..100u8)
+                            .filter(|&i| guard.insert([i; 32]).is_ok())
+                            .count()
+                    })
+                })
+                .collect();
+            workers.into_iter().map(|w| w.join().unwrap()).sum()
+        });
+        assert_eq!(accepted, 100);
+    }
+}
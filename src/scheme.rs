@@ -0,0 +1,113 @@
+//! Versioned layouts of the input to the hash function.
+
+use crate::NONCE_SIZE;
+
+/// How the `nonce` and the `bytes` are laid out in the input to Blake3.
+///
+/// Each scheme has a version number, so that a proof can record which layout
+/// it was computed with. Proofs made with one scheme are not valid under any
+/// other, and proofs made with an older scheme keep verifying under it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Scheme {
+    /// The `nonce` followed by the `bytes`, as used by `search` and `verify`.
+    /// Every attempt hashes all of the `bytes`.
+    #[default]
+    NonceFirst,
+    /// The `bytes` followed by the `nonce`. The hasher state after absorbing
+    /// the `bytes` is computed once and reused for every attempt, so the cost
+    /// of an attempt does not depend on the length of the `bytes`.
+    NonceLast,
+    /// The `nonce` followed by the Blake3 hash of the `bytes`. The `bytes`
+    /// are hashed once, and attempts then only hash the `nonce` and that
+    /// 32-byte digest, so a verifier which already has the digest need not
+    /// look at the `bytes` at all. See `search_digest` and `verify_digest`.
+    Prehashed,
+}
+
+impl Scheme {
+    /// Every scheme, in order of version.
+    pub const ALL: [Scheme; 3] = [Scheme::NonceFirst, Scheme::NonceLast, Scheme::Prehashed];
+
+    /// The version number identifying this scheme.
+    pub fn version(self) -> u8 {
+        match self {
+            Scheme::NonceFirst => 1,
+            Scheme::NonceLast => 2,
+            Scheme::Prehashed => 3,
+        }
+    }
+
+    /// The scheme with the given version number, if there is one.
+    pub fn from_version(version: u8) -> Option<Scheme> {
+        Scheme::ALL
+            .into_iter()
+            .find(|scheme| scheme.version() == version)
+    }
+}
+
+/// Hashes `nonce`s together with fixed `bytes` under some `Scheme`, doing as
+/// much of the work up front as that scheme allows.
+pub(crate) enum SchemeHasher<'a> {
+    NonceFirst(&'a [u8]),
+    /// The hasher state after absorbing the `bytes`.
+    NonceLast(Box<blake3::Hasher>),
+    /// The digest of the `bytes`.
+    Prehashed([u8; blake3::OUT_LEN]),
+}
+
+impl<'a> SchemeHasher<'a> {
+    pub(crate) fn new(scheme: Scheme, bytes: &'a [u8]) -> SchemeHasher<'a> {
+        match scheme {
+            Scheme::NonceFirst => SchemeHasher::NonceFirst(bytes),
+            Scheme::NonceLast => {
+                let mut prefix = blake3::Hasher::new();
+                prefix.update(bytes);
+                SchemeHasher::NonceLast(Box::new(prefix))
+            }
+            Scheme::Prehashed => SchemeHasher::Prehashed(blake3::hash(bytes).into()),
+        }
+    }
+
+    pub(crate) fn hash(&self, nonce: &[u8; NONCE_SIZE]) -> blake3::Hash {
+        match self {
+            SchemeHasher::NonceFirst(bytes) => crate::hash(bytes, nonce),
+            SchemeHasher::NonceLast(prefix) => {
+                let mut hasher = blake3::Hasher::clone(prefix);
+                hasher.update(nonce);
+                hasher.finalize()
+            }
+            SchemeHasher::Prehashed(digest) => crate::hash(digest, nonce),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn versions_round_trip() {
+        for scheme in Scheme::ALL {
+            assert_eq!(Scheme::from_version(scheme.version()), Some(scheme));
+        }
+        assert_eq!(Scheme::from_version(0), None);
+    }
+
+    #[test]
+    fn layouts_match_their_descriptions() {
+        let bytes = b"124124125124214121";
+        let nonce = [3; NONCE_SIZE];
+        assert_eq!(
+            SchemeHasher::new(Scheme::NonceFirst, bytes).hash(&nonce),
+            blake3::hash(&[&nonce[..], &bytes[..]].concat())
+        );
+        assert_eq!(
+            SchemeHasher::new(Scheme::NonceLast, bytes).hash(&nonce),
+            blake3::hash(&[&bytes[..], &nonce[..]].concat())
+        );
+        assert_eq!(
+            SchemeHasher::new(Scheme::Prehashed, bytes).hash(&nonce),
+            blake3::hash(&[&nonce[..], blake3::hash(bytes).as_bytes()].concat())
+        );
+    }
+}
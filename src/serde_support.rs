@@ -0,0 +1,185 @@
+//! Support for serializing with serde, enabled by the `serde` feature.
+//!
+//! Byte strings such as `nonce`s are serialized as unpadded URL-safe base64
+//! strings in human-readable formats like JSON, and as raw bytes otherwise.
+//! A `Proof` is serialized as its canonical encoding in the same way, and a
+//! `Scheme` as its version number, so that serialized values remain readable
+//! by later versions of this crate.
+//!
+//! To serialize a bare `nonce` in the same way, use
+//! `#[serde(with = "blake3_proof_of_work::serde_support::array")]`.
+
+use crate::proof::Proof;
+use crate::Scheme;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde::de::{self, Deserializer, SeqAccess, Visitor};
+use serde::{Deserialize, Serialize, Serializer};
+use std::fmt;
+
+/// Serializes fixed-length byte arrays, such as `nonce`s.
+pub mod array {
+    use super::*;
+
+    pub fn serialize<S: Serializer, const N: usize>(
+        bytes: &[u8; N],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        vec::serialize(bytes, serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>, const N: usize>(
+        deserializer: D,
+    ) -> Result<[u8; N], D::Error> {
+        let bytes = vec::deserialize(deserializer)?;
+        let len = bytes.len();
+        bytes
+            .try_into()
+            .map_err(|_| de::Error::invalid_length(len, &&*format!("{} bytes", N)))
+    }
+}
+
+/// Serializes variable-length byte strings.
+pub(crate) mod vec {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&URL_SAFE_NO_PAD.encode(bytes))
+        } else {
+            serializer.serialize_bytes(bytes)
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_str(BytesVisitor)
+        } else {
+            deserializer.deserialize_bytes(BytesVisitor)
+        }
+    }
+}
+
+struct BytesVisitor;
+
+impl<'de> Visitor<'de> for BytesVisitor {
+    type Value = Vec<u8>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "bytes or an unpadded URL-safe base64 string")
+    }
+
+    fn visit_str<E: de::Error>(self, s: &str) -> Result<Vec<u8>, E> {
+        URL_SAFE_NO_PAD
+            .decode(s)
+            .map_err(|_| E::invalid_value(de::Unexpected::Str(s), &self))
+    }
+
+    fn visit_bytes<E: de::Error>(self, bytes: &[u8]) -> Result<Vec<u8>, E> {
+        Ok(bytes.to_vec())
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, bytes: Vec<u8>) -> Result<Vec<u8>, E> {
+        Ok(bytes)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<u8>, A::Error> {
+        let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(byte) = seq.next_element()? {
+            bytes.push(byte);
+        }
+        Ok(bytes)
+    }
+}
+
+impl Serialize for Scheme {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u8(self.version())
+    }
+}
+
+impl<'de> Deserialize<'de> for Scheme {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Scheme, D::Error> {
+        let version = u8::deserialize(deserializer)?;
+        Scheme::from_version(version).ok_or_else(|| {
+            de::Error::invalid_value(
+                de::Unexpected::Unsigned(version.into()),
+                &"a scheme version",
+            )
+        })
+    }
+}
+
+impl Serialize for Proof {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.collect_str(self)
+        } else {
+            serializer.serialize_bytes(&self.to_bytes())
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Proof {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Proof, D::Error> {
+        let bytes = vec::deserialize(deserializer)?;
+        Proof::from_bytes(&bytes).map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::challenge::{verify_challenge_solution, Challenge};
+    use crate::proof::Proof;
+    use crate::{search_best, Scheme, Solution, Target};
+    use std::time::Duration;
+
+    #[test]
+    fn proofs_round_trip() -> Result<(), crate::Error> {
+        let bytes = b"124124125124214121";
+        let proof = Proof::search(bytes, 12, 100000000, Scheme::NonceLast)?;
+        let json = serde_json::to_string(&proof).unwrap();
+        assert_eq!(json, format!("\"{}\"", proof));
+        let parsed: Proof = serde_json::from_str(&json).unwrap();
+        assert!(parsed.verify(bytes, 12));
+        let binary = bincode::serialize(&proof).unwrap();
+        assert_eq!(&binary[8..], &proof.to_bytes());
+        assert_eq!(bincode::deserialize::<Proof>(&binary).unwrap(), proof);
+        assert!(serde_json::from_str::<Proof>("\"AQkAAAAWAAECAwQFBgcI_w\"").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn challenges_round_trip() -> Result<(), crate::Error> {
+        let key = [7; blake3::KEY_LEN];
+        let resource = b"GET /widgets";
+        let challenge = Challenge::issue(&key, resource, 8, Duration::from_secs(60))?;
+        let nonce = challenge.solve(100000000)?;
+        let json = serde_json::to_string(&challenge).unwrap();
+        let parsed: Challenge = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            verify_challenge_solution(&key, &parsed, resource, nonce),
+            Ok(())
+        );
+        let binary = bincode::serialize(&challenge).unwrap();
+        let parsed: Challenge = bincode::deserialize(&binary).unwrap();
+        assert_eq!(
+            verify_challenge_solution(&key, &parsed, resource, nonce),
+            Ok(())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn parameters_round_trip() -> Result<(), crate::Error> {
+        let solution = search_best(b"124124125124214121", 200, 100)?;
+        let json = serde_json::to_value(solution).unwrap();
+        assert!(json["nonce"].is_string());
+        assert_eq!(serde_json::from_value::<Solution>(json).unwrap(), solution);
+        assert_eq!(serde_json::to_string(&Scheme::Prehashed).unwrap(), "3");
+        let target = Target::from_work(1e6);
+        let json = serde_json::to_string(&target).unwrap();
+        assert_eq!(serde_json::from_str::<Target>(&json).unwrap(), target);
+        Ok(())
+    }
+}
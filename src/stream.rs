@@ -0,0 +1,97 @@
+//! Proofs of work over inputs too large to hold in memory.
+//!
+//! These all use `Scheme::Prehashed`: the input is read once to compute its
+//! digest, and the proof is then computed over that digest. A proof found by
+//! `search_reader` is therefore also checked by `verify_digest`, and by
+//! `verify_scheme` given the whole input.
+
+use crate::{search_digest, verify_digest, Error, NONCE_SIZE};
+use std::io::Read;
+
+/// # Proof search over a reader
+///
+/// Reads `reader` to the end, and then performs the same search as
+/// `search_digest` over the digest of what was read.
+pub fn search_reader(reader: impl Read, cost: u32, meter: u32) -> Result<[u8; NONCE_SIZE], Error> {
+    search_digest(&digest_reader(reader)?, cost, meter)
+}
+
+/// # Proof verification over a reader
+///
+/// Reads `reader` to the end, and then performs the same check as
+/// `verify_digest` over the digest of what was read.
+pub fn verify_reader(
+    reader: impl Read,
+    nonce: [u8; NONCE_SIZE],
+    cost: u32,
+) -> std::io::Result<bool> {
+    Ok(verify_digest(&digest_reader(reader)?, nonce, cost))
+}
+
+/// # Proof search over a file
+///
+/// Performs the same search as `search_reader` over the file at `path`,
+/// which is memory-mapped rather than read if it is large enough for that to
+/// help.
+#[cfg(feature = "mmap")]
+pub fn search_file(
+    path: impl AsRef<std::path::Path>,
+    cost: u32,
+    meter: u32,
+) -> Result<[u8; NONCE_SIZE], Error> {
+    search_digest(&digest_file(path)?, cost, meter)
+}
+
+/// # Proof verification over a file
+///
+/// Performs the same check as `verify_reader` over the file at `path`, which
+/// is memory-mapped rather than read if it is large enough for that to help.
+#[cfg(feature = "mmap")]
+pub fn verify_file(
+    path: impl AsRef<std::path::Path>,
+    nonce: [u8; NONCE_SIZE],
+    cost: u32,
+) -> std::io::Result<bool> {
+    Ok(verify_digest(&digest_file(path)?, nonce, cost))
+}
+
+fn digest_reader(reader: impl Read) -> std::io::Result<[u8; blake3::OUT_LEN]> {
+    Ok(blake3::Hasher::new()
+        .update_reader(reader)?
+        .finalize()
+        .into())
+}
+
+#[cfg(feature = "mmap")]
+fn digest_file(path: impl AsRef<std::path::Path>) -> std::io::Result<[u8; blake3::OUT_LEN]> {
+    Ok(blake3::Hasher::new().update_mmap(path)?.finalize().into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{verify_scheme, Scheme};
+
+    #[test]
+    fn search_reader_works() -> Result<(), Error> {
+        let bytes = vec![7u8; 1 << 20];
+        let nonce = search_reader(&bytes[..], 12, 100000000)?;
+        assert!(verify_reader(&bytes[..], nonce, 12)?);
+        assert!(verify_scheme(&bytes, nonce, 12, Scheme::Prehashed));
+        Ok(())
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn search_file_works() -> Result<(), Error> {
+        let bytes = vec![7u8; 1 << 20];
+        let path = std::env::temp_dir().join(format!("proof-of-work-{}", std::process::id()));
+        std::fs::write(&path, &bytes)?;
+        let nonce = search_file(&path, 12, 100000000)?;
+        let verified = verify_file(&path, nonce, 12)?;
+        std::fs::remove_file(&path)?;
+        assert!(verified);
+        assert!(verify_reader(&bytes[..], nonce, 12)?);
+        Ok(())
+    }
+}
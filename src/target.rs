@@ -0,0 +1,140 @@
+//! Difficulty expressed as a 256-bit threshold, in the style of Bitcoin.
+
+/// A threshold which a hash must not exceed, read as a big-endian 256-bit
+/// integer, in order to constitute a proof of work.
+///
+/// Unlike a `cost`, which can only double or halve the expected work, a
+/// `Target` can express any expected work between one and `2^256` attempts.
+/// The target corresponding to a `cost` is met by exactly those hashes with
+/// at least `cost` leading zeros.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Target(
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_support::array"))]
+    [u8; blake3::OUT_LEN],
+);
+
+impl Target {
+    /// The easiest target, met by every hash.
+    pub const MAX: Target = Target([0xff; blake3::OUT_LEN]);
+
+    /// The hardest target, met only by the all-zero hash.
+    pub const MIN: Target = Target([0; blake3::OUT_LEN]);
+
+    /// The target met by those hashes with at least `cost` leading zeros.
+    pub fn from_cost(cost: u32) -> Target {
+        let mut bytes = [0xff; blake3::OUT_LEN];
+        let cost = cost.min(8 * blake3::OUT_LEN as u32) as usize;
+        bytes[..cost / 8].fill(0);
+        if cost / 8 < blake3::OUT_LEN {
+            bytes[cost / 8] >>= cost % 8;
+        }
+        Target(bytes)
+    }
+
+    /// The target for which a search is expected to need `work` attempts.
+    ///
+    /// A `work` of `2^cost` gives the same target as `from_cost(cost)`, up to
+    /// the precision of an `f64`. Any `work` of one or less gives `MAX`.
+    pub fn from_work(work: f64) -> Target {
+        if work.is_nan() || work <= 1.0 {
+            return Target::MAX;
+        }
+        // The target is 2^256 / work - 1, and 2^256 / work has at most 53
+        // significant bits, so we place its mantissa into a 256-bit integer
+        // and subtract one.
+        let quotient = 2f64.powi(256) / work;
+        if quotient < 1.0 {
+            return Target::MIN;
+        }
+        let bits = quotient.to_bits();
+        let exponent = ((bits >> 52) & 0x7ff) as i32 - 1075;
+        let mantissa = (bits & ((1 << 52) - 1)) | (1 << 52);
+        let mut bytes = [0u8; blake3::OUT_LEN];
+        for bit in 0..53 {
+            if mantissa & (1 << bit) == 0 {
+                continue;
+            }
+            let position = bit + exponent;
+            if (0..256).contains(&position) {
+                let position = position as usize;
+                bytes[blake3::OUT_LEN - 1 - position / 8] |= 1 << (position % 8);
+            }
+        }
+        for byte in bytes.iter_mut().rev() {
+            let (difference, borrow) = byte.overflowing_sub(1);
+            *byte = difference;
+            if !borrow {
+                break;
+            }
+        }
+        Target(bytes)
+    }
+
+    /// Constructs a target from its big-endian representation.
+    pub fn from_bytes(bytes: [u8; blake3::OUT_LEN]) -> Target {
+        Target(bytes)
+    }
+
+    /// The big-endian representation of this target.
+    pub fn as_bytes(&self) -> &[u8; blake3::OUT_LEN] {
+        &self.0
+    }
+
+    /// The expected number of attempts a search for this target needs.
+    pub fn work(&self) -> f64 {
+        let target = self
+            .0
+            .iter()
+            .fold(0f64, |acc, &byte| acc * 256.0 + byte as f64);
+        2f64.powi(256) / (target + 1.0)
+    }
+
+    /// Whether the given `hash` meets this target.
+    pub fn is_met_by(&self, hash: &[u8; blake3::OUT_LEN]) -> bool {
+        hash <= &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::leading_zeros;
+
+    #[test]
+    fn from_cost_matches_leading_zeros() {
+        for cost in [0, 1, 7, 8, 9, 20, 255, 256] {
+            let target = Target::from_cost(cost);
+            assert_eq!(leading_zeros(target.as_bytes()), cost);
+            assert!(target.is_met_by(target.as_bytes()));
+            let mut easier = *target.as_bytes();
+            if cost > 0 {
+                easier[(cost as usize - 1) / 8] |= 0x80 >> ((cost - 1) % 8);
+                assert!(!target.is_met_by(&easier));
+            }
+        }
+        assert_eq!(Target::from_cost(0), Target::MAX);
+        assert_eq!(Target::from_cost(256), Target::MIN);
+    }
+
+    #[test]
+    fn from_work_matches_from_cost() {
+        for cost in [1, 8, 20, 52, 100, 255] {
+            assert_eq!(
+                Target::from_work(2f64.powi(cost)),
+                Target::from_cost(cost as u32)
+            );
+        }
+        assert_eq!(Target::from_work(0.5), Target::MAX);
+        assert_eq!(Target::from_work(f64::INFINITY), Target::MIN);
+    }
+
+    #[test]
+    fn work_is_fractional() {
+        let base = Target::from_cost(20);
+        let harder = Target::from_work(1.2 * base.work());
+        assert!(harder < base);
+        assert!(Target::from_cost(21) < harder);
+        assert!((harder.work() / base.work() - 1.2).abs() < 1e-9);
+    }
+}